@@ -0,0 +1,43 @@
+//! Counts the leaf nodes of the game tree.
+//!
+//! Usage: `cargo run --release --example perft -- <depth> [sfen] [--divide]`
+
+use std::env;
+use std::process;
+use std::time::Instant;
+
+use lewek::{divide, perft, Position};
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let divide_mode = args.iter().any(|arg| arg == "--divide");
+    let args: Vec<&String> = args.iter().filter(|arg| *arg != "--divide").collect();
+
+    let Some(depth) = args.first().and_then(|depth| depth.parse().ok()) else {
+        eprintln!("usage: perft <depth> [sfen] [--divide]");
+        process::exit(1);
+    };
+
+    let mut position = match args.get(1) {
+        Some(sfen) => sfen.parse().unwrap_or_else(|e| {
+            eprintln!("{}", e);
+            process::exit(1);
+        }),
+        None => Position::startpos(),
+    };
+
+    let start = Instant::now();
+    let nodes = if divide_mode {
+        let moves = divide(&mut position, depth);
+        for (mv, nodes) in &moves {
+            println!("{}: {}", position.move_notation(*mv), nodes);
+        }
+        moves.iter().map(|(_, nodes)| nodes).sum()
+    } else {
+        perft(&mut position, depth)
+    };
+
+    println!();
+    println!("nodes: {}", nodes);
+    println!("time:  {:.3}s", start.elapsed().as_secs_f64());
+}
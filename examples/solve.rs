@@ -0,0 +1,57 @@
+//! Builds the perfect play database of the whole game and probes a position.
+//!
+//! Usage: `cargo run --release --example solve -- <output> [sfen]`
+//!
+//! This needs around 1.3 GB of memory. The position defaults to the
+//! starting position.
+
+use std::env;
+use std::process;
+use std::time::Instant;
+
+use lewek::{Database, Position, Progress};
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+
+    let Some(output) = args.first() else {
+        eprintln!("usage: solve <output> [sfen]");
+        process::exit(1);
+    };
+
+    let position = match args.get(1) {
+        Some(sfen) => sfen.parse().unwrap_or_else(|e| {
+            eprintln!("{}", e);
+            process::exit(1);
+        }),
+        None => Position::startpos(),
+    };
+
+    let start = Instant::now();
+    let database = Database::solve_with_progress(|progress| match progress {
+        Progress::Indexed { positions } => println!("{} positions", positions),
+        Progress::Solved {
+            distance,
+            positions,
+        } => println!("distance {:3}: {} positions", distance, positions),
+    });
+    println!("solved in {:.3}s", start.elapsed().as_secs_f64());
+
+    if let Err(e) = database.save(output) {
+        eprintln!("{}: {}", output, e);
+        process::exit(1);
+    }
+
+    let solution = position
+        .probe(&database)
+        .expect("the database holds every position of a game");
+    println!(
+        "{}: {:?} in {} plies, best move {}",
+        position,
+        solution.outcome,
+        solution.distance,
+        solution
+            .best_move
+            .map_or("none".to_owned(), |mv| position.move_notation(mv))
+    );
+}
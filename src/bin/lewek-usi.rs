@@ -0,0 +1,472 @@
+//! Engine speaking the USI protocol, for shogi GUIs and tournament managers.
+
+use std::io::{self, BufRead};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+use lewek::{
+    Color, GameState, Position, Score, SearchLimits, SearchResult, Searcher, Weights,
+    DEFAULT_TT_SIZE_MB,
+};
+
+const NAME: &str = "lewek";
+/// Time kept back from every move for the communication with the GUI.
+const MOVE_OVERHEAD: Duration = Duration::from_millis(50);
+/// Share of the remaining time spent on a single move.
+const MOVES_TO_GO: u32 = 20;
+
+/// Limits of a `go` command.
+#[derive(Debug, Default, PartialEq, Eq)]
+struct Go {
+    limits: SearchLimits,
+    /// Don't answer before `stop`, even if the search finished.
+    infinite: bool,
+    /// Search on the opponent's time without answering before `ponderhit`
+    /// or `stop`. The time limit only starts on `ponderhit`.
+    ponder: bool,
+}
+
+/// Time for a move, given the remaining time, the increment and the byoyomi.
+/// The increment is only credited after the move, so the budget never
+/// exceeds the time left on the clock.
+fn time_budget(
+    remaining: Option<Duration>,
+    increment: Duration,
+    byoyomi: Duration,
+) -> Option<Duration> {
+    if remaining.is_none() && byoyomi.is_zero() {
+        return None;
+    }
+    let remaining = remaining.unwrap_or_default();
+    let budget = (remaining / MOVES_TO_GO + increment + byoyomi).min(remaining + byoyomi);
+    Some(
+        budget
+            .saturating_sub(MOVE_OVERHEAD)
+            .max(Duration::from_millis(1)),
+    )
+}
+
+fn parse_go(args: &[&str], to_play: Color) -> Result<Go, String> {
+    let mut go = Go::default();
+    let (mut remaining, mut increment, mut byoyomi) = (None, Duration::ZERO, Duration::ZERO);
+
+    let mut args = args.iter();
+    while let Some(&arg) = args.next() {
+        let mut value = || -> Result<u64, String> {
+            args.next()
+                .and_then(|value| value.parse().ok())
+                .ok_or(format!("'{}' needs a number", arg))
+        };
+        match (arg, to_play) {
+            ("infinite", _) => go.infinite = true,
+            ("ponder", _) => go.ponder = true,
+            ("depth", _) => go.limits.depth = Some(value()? as usize),
+            ("nodes", _) => go.limits.nodes = Some(value()?),
+            ("movetime", _) => go.limits.time = Some(Duration::from_millis(value()?)),
+            ("byoyomi", _) => byoyomi = Duration::from_millis(value()?),
+            ("btime", Color::Black) | ("wtime", Color::White) => {
+                remaining = Some(Duration::from_millis(value()?))
+            }
+            ("binc", Color::Black) | ("winc", Color::White) => {
+                increment = Duration::from_millis(value()?)
+            }
+            ("btime" | "wtime" | "binc" | "winc", _) => {
+                value()?;
+            }
+            _ => return Err(format!("unknown go argument '{}'", arg)),
+        }
+    }
+
+    if !go.infinite && go.limits.time.is_none() {
+        go.limits.time = time_budget(remaining, increment, byoyomi);
+    }
+    Ok(go)
+}
+
+/// Parses the arguments of `position`, returning the position
+/// and the keys of the positions played before it.
+fn parse_position(args: &[&str]) -> Result<(Position, Vec<u64>), String> {
+    let split = args.iter().position(|&arg| arg == "moves");
+    let (setup, moves) = match split {
+        Some(split) => (&args[..split], &args[split + 1..]),
+        None => (args, &[][..]),
+    };
+
+    let start = match setup {
+        ["startpos"] => Position::startpos(),
+        ["sfen", sfen @ ..] => sfen.join(" ").parse().map_err(|e| format!("{}", e))?,
+        _ => return Err("expected 'startpos' or 'sfen'".to_owned()),
+    };
+
+    let mut game = GameState::new(start);
+    for text in moves {
+        let mv = game
+            .current_position
+            .parse_move(text)
+            .map_err(|e| format!("{}", e))?;
+        game.make_move(mv);
+    }
+    Ok((game.current_position, game.previous_keys))
+}
+
+fn score_text(score: Score) -> String {
+    match score {
+        Score::Value(value) => format!("cp {}", value),
+        Score::Win(plies) => format!("mate {}", plies),
+        Score::Loss(plies) => format!("mate -{}", plies),
+    }
+}
+
+fn info_line(position: &Position, result: &SearchResult) -> String {
+    let mut position = *position;
+    let pv: Vec<String> = result
+        .pv
+        .iter()
+        .map(|&mv| {
+            let notation = position.move_notation(mv);
+            position.make_move(mv);
+            notation
+        })
+        .collect();
+    let millis = result.time.as_millis() as u64;
+
+    format!(
+        "info depth {} score {} nodes {} nps {} time {} pv {}",
+        result.depth,
+        score_text(result.score()),
+        result.nodes,
+        result.nodes * 1000 / millis.max(1),
+        millis,
+        pv.join(" ")
+    )
+}
+
+struct Engine {
+    /// `None` after a `position` command which failed, until a valid one.
+    position: Option<Position>,
+    history: Vec<u64>,
+    /// `None` while a search thread owns it.
+    searcher: Option<Searcher>,
+    /// The running search, and whether it is infinite.
+    search: Option<(JoinHandle<Searcher>, bool)>,
+    stop: Arc<AtomicBool>,
+    /// Set while a `go ponder` search waits for `ponderhit`.
+    pondering: Arc<AtomicBool>,
+    /// Time limit of the pondering search, applied from `ponderhit` on.
+    ponder_time: Option<Duration>,
+    /// Number of searches started, so that a late `ponderhit` timer
+    /// doesn't stop a later search.
+    searches: Arc<AtomicU64>,
+    tt_size: usize,
+}
+
+impl Engine {
+    fn new() -> Self {
+        let stop = Arc::new(AtomicBool::new(false));
+        Engine {
+            position: Some(Position::startpos()),
+            history: vec![],
+            searcher: Some(Searcher::new(SearchLimits::default()).with_stop(stop.clone())),
+            search: None,
+            stop,
+            pondering: Arc::new(AtomicBool::new(false)),
+            ponder_time: None,
+            searches: Arc::new(AtomicU64::new(0)),
+            tt_size: DEFAULT_TT_SIZE_MB,
+        }
+    }
+
+    /// Stops the running search, if any, waiting for its best move.
+    fn stop_search(&mut self) {
+        self.pondering.store(false, Ordering::Relaxed);
+        self.stop.store(true, Ordering::Relaxed);
+        self.wait_search();
+    }
+
+    /// Waits until the running search, if any, reaches its limits.
+    /// Infinite searches are stopped instead.
+    fn wait_search(&mut self) {
+        if let Some((search, infinite)) = self.search.take() {
+            if infinite {
+                self.stop.store(true, Ordering::Relaxed);
+            }
+            self.searcher = Some(search.join().expect("search thread panicked"));
+        }
+    }
+
+    fn searcher(&mut self) -> &mut Searcher {
+        self.wait_search();
+        self.searcher.as_mut().unwrap()
+    }
+
+    fn set_option(&mut self, args: &[&str]) -> Result<(), String> {
+        let (name, value) = match args {
+            ["name", name, "value", value @ ..] => (*name, value.join(" ")),
+            ["name", name] => (*name, String::new()),
+            _ => return Err("expected 'name <id> value <x>'".to_owned()),
+        };
+        match name {
+            "USI_Hash" => {
+                let size = value.parse().map_err(|_| "USI_Hash needs a number")?;
+                self.wait_search();
+                let searcher = self.searcher.take().unwrap();
+                self.searcher = Some(searcher.with_tt_size(size));
+                self.tt_size = size;
+            }
+            "Weights" if value.is_empty() || value == "<empty>" => {
+                self.searcher().set_weights(Weights::default());
+            }
+            "Weights" => {
+                let weights = Weights::load(&value).map_err(|e| format!("{}: {}", value, e))?;
+                self.searcher().set_weights(weights);
+            }
+            "USI_Ponder" => {}
+            _ => return Err(format!("unknown option '{}'", name)),
+        }
+        Ok(())
+    }
+
+    fn go(&mut self, args: &[&str]) -> Result<(), String> {
+        let position = self
+            .position
+            .ok_or("no valid position, the last 'position' command failed")?;
+        let go = parse_go(args, position.to_play())?;
+        self.wait_search();
+
+        let mut limits = go.limits;
+        self.ponder_time = if go.ponder { limits.time.take() } else { None };
+        self.pondering.store(go.ponder, Ordering::Relaxed);
+        self.searches.fetch_add(1, Ordering::Relaxed);
+
+        let mut searcher = self.searcher.take().unwrap();
+        searcher.set_limits(limits);
+        searcher.set_history(&self.history);
+        self.stop.store(false, Ordering::Relaxed);
+
+        let stop = self.stop.clone();
+        let pondering = self.pondering.clone();
+        let infinite = go.infinite;
+        let search = thread::spawn(move || {
+            let result = searcher.search_with_info(&position, |result| {
+                println!("{}", info_line(&position, result));
+            });
+            while (infinite || pondering.load(Ordering::Relaxed)) && !stop.load(Ordering::Relaxed) {
+                thread::sleep(Duration::from_millis(5));
+            }
+            match result.best_move {
+                Some(mv) => println!("bestmove {}", position.move_notation(mv)),
+                None => println!("bestmove resign"),
+            }
+            searcher
+        });
+        self.search = Some((search, go.infinite || go.ponder));
+        Ok(())
+    }
+
+    /// Turns the pondering search into a normal one, which stops
+    /// once the time for the move is used up.
+    fn ponderhit(&mut self) {
+        if !self.pondering.swap(false, Ordering::Relaxed) {
+            return;
+        }
+        if let Some((_, infinite)) = self.search.as_mut() {
+            *infinite = false;
+        }
+        if let Some(time) = self.ponder_time.take() {
+            let stop = self.stop.clone();
+            let searches = self.searches.clone();
+            let search = searches.load(Ordering::Relaxed);
+            thread::spawn(move || {
+                thread::sleep(time);
+                if searches.load(Ordering::Relaxed) == search {
+                    stop.store(true, Ordering::Relaxed);
+                }
+            });
+        }
+    }
+
+    /// Handles a command, returning `false` on `quit`.
+    fn command(&mut self, line: &str) -> bool {
+        let words: Vec<&str> = line.split_whitespace().collect();
+        let Some((&command, args)) = words.split_first() else {
+            return true;
+        };
+
+        let outcome = match command {
+            "usi" => {
+                println!("id name {} {}", NAME, env!("CARGO_PKG_VERSION"));
+                println!("id author the {} authors", NAME);
+                println!(
+                    "option name USI_Hash type spin default {} min 1 max 4096",
+                    self.tt_size
+                );
+                println!("option name Weights type filename default <empty>");
+                println!("usiok");
+                Ok(())
+            }
+            "isready" => {
+                println!("readyok");
+                Ok(())
+            }
+            "setoption" => self.set_option(args),
+            "usinewgame" => {
+                self.searcher().clear_tt();
+                Ok(())
+            }
+            "position" => {
+                let parsed = parse_position(args);
+                self.wait_search();
+                match parsed {
+                    Ok((position, history)) => {
+                        self.position = Some(position);
+                        self.history = history;
+                        Ok(())
+                    }
+                    Err(message) => {
+                        self.position = None;
+                        self.history.clear();
+                        Err(message)
+                    }
+                }
+            }
+            "go" => self.go(args),
+            "ponderhit" => {
+                self.ponderhit();
+                Ok(())
+            }
+            "stop" | "gameover" => {
+                self.stop_search();
+                Ok(())
+            }
+            "quit" => {
+                self.stop_search();
+                return false;
+            }
+            _ => Err(format!("unknown command '{}'", command)),
+        };
+
+        if let Err(message) = outcome {
+            println!("info string {}", message);
+            // The GUI waits for an answer to every `go`.
+            if command == "go" {
+                println!("bestmove resign");
+            }
+        }
+        true
+    }
+}
+
+fn main() {
+    let mut engine = Engine::new();
+    for line in io::stdin().lock().lines() {
+        let Ok(line) = line else { break };
+        if !engine.command(&line) {
+            return;
+        }
+    }
+    engine.stop_search();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_position_startpos_with_moves() {
+        let (position, history) = parse_position(&["startpos", "moves", "b3b2", "c1b2"]).unwrap();
+
+        assert_eq!(position.to_string(), "rk1/1b1/3/BKR b Pp");
+        assert_eq!(history.len(), 2);
+        assert_eq!(history[0], Position::startpos().key());
+    }
+
+    #[test]
+    fn test_position_sfen() {
+        let args = ["sfen", "1k1/P2/3/1K1", "b", "pb2r2", "1", "moves", "a2a1+"];
+        let (position, history) = parse_position(&args).unwrap();
+
+        assert_eq!(position.to_string(), "P+k1/3/3/1K1 w pb2r2 2");
+        assert_eq!(history.len(), 1);
+    }
+
+    #[test]
+    fn test_position_errors() {
+        assert!(parse_position(&["startpos", "moves", "b3b1"]).is_err());
+        assert!(parse_position(&["sfen", "rkb/1p1/1P1/BKR", "x", "-"]).is_err());
+        assert!(parse_position(&["nothing"]).is_err());
+    }
+
+    #[test]
+    fn test_invalid_position_is_not_searched() {
+        let mut engine = Engine::new();
+
+        assert!(engine.command("position sfen rkb/1p1/1P1/BKR x -"));
+        assert_eq!(engine.position, None);
+        assert!(engine.go(&["depth", "1"]).is_err());
+        assert!(engine.search.is_none());
+
+        assert!(engine.command("position startpos moves b3b2"));
+        assert!(engine.position.is_some());
+        assert!(engine.command("position startpos moves b3b1"));
+        assert_eq!(engine.position, None);
+    }
+
+    #[test]
+    fn test_go_limits() {
+        let go = parse_go(&["depth", "5", "nodes", "1000"], Color::Black).unwrap();
+        assert_eq!(go.limits.depth, Some(5));
+        assert_eq!(go.limits.nodes, Some(1000));
+        assert_eq!(go.limits.time, None);
+        assert!(!go.infinite);
+
+        let go = parse_go(&["infinite"], Color::Black).unwrap();
+        assert_eq!(
+            go,
+            Go {
+                limits: SearchLimits::default(),
+                infinite: true,
+                ponder: false,
+            }
+        );
+
+        let go = parse_go(&["ponder", "byoyomi", "1000"], Color::Black).unwrap();
+        assert!(go.ponder);
+        assert!(!go.infinite);
+        assert_eq!(go.limits.time, Some(Duration::from_millis(950)));
+
+        assert!(parse_go(&["depth"], Color::Black).is_err());
+        assert!(parse_go(&["sideways"], Color::Black).is_err());
+    }
+
+    #[test]
+    fn test_go_clock() {
+        let args = [
+            "btime", "60000", "wtime", "20000", "binc", "1000", "winc", "0",
+        ];
+
+        let black = parse_go(&args, Color::Black).unwrap();
+        assert_eq!(black.limits.time, Some(Duration::from_millis(3950)));
+
+        let white = parse_go(&args, Color::White).unwrap();
+        assert_eq!(white.limits.time, Some(Duration::from_millis(950)));
+
+        let byoyomi = parse_go(
+            &["btime", "0", "wtime", "0", "byoyomi", "3000"],
+            Color::White,
+        )
+        .unwrap();
+        assert_eq!(byoyomi.limits.time, Some(Duration::from_millis(2950)));
+
+        let short = parse_go(&["btime", "100", "binc", "1000"], Color::Black).unwrap();
+        assert_eq!(short.limits.time, Some(Duration::from_millis(50)));
+    }
+
+    #[test]
+    fn test_score_text() {
+        assert_eq!(score_text(Score::Value(-35)), "cp -35");
+        assert_eq!(score_text(Score::Win(3)), "mate 3");
+        assert_eq!(score_text(Score::Loss(4)), "mate -4");
+    }
+}
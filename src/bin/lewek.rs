@@ -0,0 +1,460 @@
+//! Interactive terminal tool for playing and analysing games.
+
+use std::fs;
+use std::io::{self, BufRead, Write};
+use std::path::Path;
+use std::time::Duration;
+
+use lewek::{
+    Csa, GameRecord, GameResult, Glyphs, Kif, Move, Position, Renderer, Score, SearchLimits,
+    SearchResult, Searcher, Sfen,
+};
+
+const HELP: &str = "\
+commands:
+  new [sfen]        start a new game from the start position or a SFEN
+  <move>            play a move, e.g. b3b2, a2a1+ or P*b2
+  undo, redo        take back a move or play it again
+  board             show the board
+  style <glyphs>    draw pieces as 'ascii' letters, 'kana' or 'emoji'
+  color on|off      color the pieces and highlights
+  attacks on|off    highlight the squares attacked by the side which just moved
+  sfen              show the SFEN of the position
+  moves             list the legal moves
+  go [limits]       let the engine play a move
+  eval [limits]     show the engine's evaluation and best line
+  save <file>       save the game, as KIF or CSA for .kif and .csa files
+  load <file>       load a game saved by 'save'
+  help              show this help
+  quit              leave
+limits are 'depth <plies>', 'nodes <count>' or 'time <seconds>' (default: 1 second)";
+
+const DEFAULT_THINKING_TIME: Duration = Duration::from_secs(1);
+
+fn parse_limits(args: &[&str]) -> Result<SearchLimits, String> {
+    match args {
+        [] => Ok(SearchLimits::time(DEFAULT_THINKING_TIME)),
+        ["depth", depth] => depth
+            .parse()
+            .map(SearchLimits::depth)
+            .map_err(|_| format!("invalid depth '{}'", depth)),
+        ["nodes", nodes] => nodes
+            .parse()
+            .map(SearchLimits::nodes)
+            .map_err(|_| format!("invalid node count '{}'", nodes)),
+        ["time", seconds] => seconds
+            .parse()
+            .ok()
+            .filter(|seconds: &f64| *seconds > 0.0)
+            .map(|seconds| SearchLimits::time(Duration::from_secs_f64(seconds)))
+            .ok_or(format!("invalid time '{}'", seconds)),
+        _ => Err("expected 'depth <plies>', 'nodes <count>' or 'time <seconds>'".to_owned()),
+    }
+}
+
+fn score_text(score: Score) -> String {
+    match score {
+        Score::Value(value) => format!("{:+.2}", value as f64 / 100.0),
+        Score::Win(plies) => format!("win in {}", plies),
+        Score::Loss(plies) => format!("loss in {}", plies),
+    }
+}
+
+fn line_text(position: &Position, moves: &[Move]) -> String {
+    let mut position = *position;
+    let notation: Vec<String> = moves
+        .iter()
+        .map(|&mv| {
+            let text = position.move_notation(mv);
+            position.make_move(mv);
+            text
+        })
+        .collect();
+    notation.join(" ")
+}
+
+fn result_text(result: GameResult) -> Option<&'static str> {
+    match result {
+        GameResult::Ongoing => None,
+        GameResult::BlackWin => Some("Black wins"),
+        GameResult::WhiteWin => Some("White wins"),
+        GameResult::Draw => Some("draw by repetition"),
+    }
+}
+
+struct Cli {
+    record: GameRecord,
+    searcher: Searcher,
+    glyphs: Glyphs,
+    ansi: bool,
+    /// Whether to highlight the squares attacked by the side which just moved.
+    attacks: bool,
+}
+
+impl Cli {
+    fn new() -> Self {
+        Cli {
+            record: GameRecord::new(Position::startpos()),
+            searcher: Searcher::new(SearchLimits::default()),
+            glyphs: Glyphs::Ascii,
+            ansi: false,
+            attacks: false,
+        }
+    }
+
+    fn result(&self) -> GameResult {
+        self.record.game_state().result()
+    }
+
+    /// Plays the move, making the line leading to it the main one,
+    /// so that `redo` follows it after an `undo`.
+    fn play(&mut self, text: &str, out: &mut impl Write) -> Result<(), String> {
+        if let Some(result) = result_text(self.result()) {
+            return Err(format!("the game is over: {}", result));
+        }
+        let mv = self
+            .record
+            .position()
+            .parse_move(text)
+            .map_err(|e| e.to_string())?;
+        self.record.play(mv).map_err(|e| e.to_string())?;
+        self.record.promote_variation();
+        self.record.result = self.result();
+        self.show(out)
+    }
+
+    fn show(&self, out: &mut impl Write) -> Result<(), String> {
+        let position = self.record.position();
+        let renderer = Renderer::new(self.glyphs)
+            .with_ansi(self.ansi)
+            .with_last_move(self.record.last_move())
+            .with_attacked_by(self.attacks.then(|| position.to_play().opposite()));
+        let mut text = renderer.render(position);
+        let move_number = position
+            .move_number()
+            .map_or(self.record.ply() + 1, |number| number as usize);
+        text += &format!(", move {}", move_number);
+        if let Some(result) = result_text(self.result()) {
+            text += &format!("\ngame over: {}", result);
+        }
+        writeln!(out, "{}", text).map_err(|e| e.to_string())
+    }
+
+    fn think(&mut self, args: &[&str]) -> Result<SearchResult, String> {
+        if let Some(result) = result_text(self.result()) {
+            return Err(format!("the game is over: {}", result));
+        }
+        self.searcher.set_limits(parse_limits(args)?);
+        self.searcher
+            .set_history(&self.record.game_state().previous_keys);
+        Ok(self.searcher.search(self.record.position()))
+    }
+
+    fn save(&self, path: &str) -> Result<(), String> {
+        let start = *self.record.start();
+        let moves = self.record.mainline();
+        let text = match Path::new(path).extension().and_then(|e| e.to_str()) {
+            Some("kif") => Kif {
+                result: self.record.result,
+                ..Kif::from_moves(start, &moves)
+            }
+            .to_string(),
+            Some("csa") => {
+                let mut csa = Csa::from_moves(start, &moves);
+                csa.set_result(self.record.result);
+                csa.to_string()
+            }
+            _ => self.record.to_string(),
+        };
+        fs::write(path, text).map_err(|e| format!("{}: {}", path, e))
+    }
+
+    fn load(&mut self, path: &str) -> Result<(), String> {
+        let text = fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))?;
+        let error = |e: &dyn std::fmt::Display| format!("{}: {}", path, e);
+        let mut record = match Path::new(path).extension().and_then(|e| e.to_str()) {
+            Some("kif") => {
+                let kif: Kif = text.parse().map_err(|e| error(&e))?;
+                let mut record =
+                    GameRecord::from_moves(kif.start, &kif.moves).map_err(|e| error(&e))?;
+                record.result = kif.result;
+                record
+            }
+            Some("csa") => {
+                let csa: Csa = text.parse().map_err(|e| error(&e))?;
+                let moves: Vec<_> = csa.moves.iter().map(|m| m.mv).collect();
+                let mut record =
+                    GameRecord::from_moves(csa.start, &moves).map_err(|e| error(&e))?;
+                record.result = csa.result();
+                record
+            }
+            _ => text.parse().map_err(|e| error(&e))?,
+        };
+        record.goto_end();
+        self.record = record;
+        Ok(())
+    }
+
+    /// Executes a command line, returning `Ok(false)` on `quit`.
+    fn command(&mut self, line: &str, out: &mut impl Write) -> Result<bool, String> {
+        let words: Vec<&str> = line.split_whitespace().collect();
+        let Some((&command, args)) = words.split_first() else {
+            return Ok(true);
+        };
+        let output = |out: &mut dyn Write, text: &str| -> Result<(), String> {
+            writeln!(out, "{}", text).map_err(|e| e.to_string())
+        };
+
+        match (command, args) {
+            ("quit" | "exit", []) => return Ok(false),
+            ("help", []) => output(out, HELP)?,
+            ("new", []) => {
+                self.record = GameRecord::new(Position::startpos());
+                self.searcher.clear_tt();
+                self.show(out)?;
+            }
+            ("new", sfen) => {
+                let start: Position = sfen.join(" ").parse().map_err(|e| format!("{}", e))?;
+                self.record = GameRecord::new(start);
+                self.searcher.clear_tt();
+                self.show(out)?;
+            }
+            ("undo", []) => {
+                if !self.record.back() {
+                    return Err("no move to undo".to_owned());
+                }
+                self.show(out)?;
+            }
+            ("redo", []) => {
+                if !self.record.forward() {
+                    return Err("no move to redo".to_owned());
+                }
+                self.show(out)?;
+            }
+            ("board", []) => self.show(out)?,
+            ("sfen", []) => output(out, &self.record.position().sfen())?,
+            ("moves", []) => {
+                let position = self.record.position();
+                let moves: Vec<String> = position
+                    .legal_moves()
+                    .into_iter()
+                    .map(|mv| position.move_notation(mv))
+                    .collect();
+                output(out, &moves.join(" "))?;
+            }
+            ("go", limits) => {
+                let result = self.think(limits)?;
+                let mv = result.best_move.ok_or("no legal move")?;
+                let text = self.record.position().move_notation(mv);
+                output(
+                    out,
+                    &format!("engine plays {} ({})", text, score_text(result.score())),
+                )?;
+                self.play(&text, out)?;
+            }
+            ("eval", limits) => {
+                let result = self.think(limits)?;
+                output(
+                    out,
+                    &format!(
+                        "{} at depth {}: {}",
+                        score_text(result.score()),
+                        result.depth,
+                        line_text(self.record.position(), &result.pv)
+                    ),
+                )?;
+            }
+            ("style", [style]) => {
+                self.glyphs = match *style {
+                    "ascii" => Glyphs::Ascii,
+                    "kana" => Glyphs::Kana,
+                    "emoji" => Glyphs::Emoji,
+                    _ => return Err("expected 'ascii', 'kana' or 'emoji'".to_owned()),
+                };
+                self.show(out)?;
+            }
+            ("color" | "attacks", [switch]) => {
+                let on = match *switch {
+                    "on" => true,
+                    "off" => false,
+                    _ => return Err("expected 'on' or 'off'".to_owned()),
+                };
+                if command == "color" {
+                    self.ansi = on;
+                } else {
+                    self.attacks = on;
+                }
+                self.show(out)?;
+            }
+            ("save", [path]) => self.save(path)?,
+            ("load", [path]) => {
+                self.load(path)?;
+                self.show(out)?;
+            }
+            ("move", [mv]) => self.play(mv, out)?,
+            (mv, []) if mv.parse::<Move>().is_ok() => self.play(mv, out)?,
+            _ => return Err(format!("unknown command '{}', try 'help'", line.trim())),
+        }
+        Ok(true)
+    }
+}
+
+fn main() {
+    let mut cli = Cli::new();
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let mut stdout = io::stdout();
+    if !args.is_empty() {
+        if let Err(message) = cli.command(&format!("new {}", args.join(" ")), &mut io::sink()) {
+            eprintln!("{}", message);
+            std::process::exit(1);
+        }
+    }
+    let _ = cli.show(&mut stdout);
+
+    let stdin = io::stdin();
+    loop {
+        print!("> ");
+        let _ = stdout.flush();
+        let mut line = String::new();
+        match stdin.lock().read_line(&mut line) {
+            Ok(0) | Err(_) => break,
+            Ok(_) => {}
+        }
+        match cli.command(&line, &mut stdout) {
+            Ok(true) => {}
+            Ok(false) => break,
+            Err(message) => println!("error: {}", message),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(cli: &mut Cli, line: &str) -> String {
+        let mut out = vec![];
+        cli.command(line, &mut out).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn test_board() {
+        let mut cli = Cli::new();
+
+        assert_eq!(
+            run(&mut cli, "board"),
+            "\
+White hand: -
+     a     b     c
+  +-----+-----+-----+
+1 |  r  |  k  |  b  |
+  +-----+-----+-----+
+2 |     |  p  |     |
+  +-----+-----+-----+
+3 |     |  P  |     |
+  +-----+-----+-----+
+4 |  B  |  K  |  R  |
+  +-----+-----+-----+
+Black hand: -
+Black to play, move 1
+"
+        );
+    }
+
+    #[test]
+    fn test_moves_undo_redo() {
+        let mut cli = Cli::new();
+        run(&mut cli, "b3b2");
+        run(&mut cli, "c1b2");
+        assert_eq!(run(&mut cli, "sfen"), "rk1/1b1/3/BKR b Pp\n");
+
+        run(&mut cli, "undo");
+        run(&mut cli, "undo");
+        assert_eq!(run(&mut cli, "sfen"), "rkb/1p1/1P1/BKR b -\n");
+        run(&mut cli, "redo");
+        assert_eq!(cli.record.ply(), 1);
+
+        run(&mut cli, "b1a2");
+        run(&mut cli, "undo");
+        run(&mut cli, "redo");
+        assert_eq!(cli.record.last_move().unwrap().to_string(), "b1a2");
+
+        assert!(cli.command("b3b1", &mut vec![]).is_err());
+        assert!(cli.command("undo", &mut vec![]).is_ok());
+        assert!(cli.command("undo", &mut vec![]).is_ok());
+        assert!(cli.command("undo", &mut vec![]).is_err());
+    }
+
+    #[test]
+    fn test_move_number() {
+        let mut cli = Cli::new();
+
+        assert!(run(&mut cli, "new 1k1/P2/3/1K1 b pb2r2 5").ends_with(", move 5\n"));
+        assert!(run(&mut cli, "a2a1+").ends_with(", move 6\n"));
+        assert!(run(&mut cli, "new").ends_with(", move 1\n"));
+    }
+
+    #[test]
+    fn test_legal_moves() {
+        let mut cli = Cli::new();
+
+        assert_eq!(run(&mut cli, "moves"), "b3b2 b4a3 b4c3 c4c3\n");
+    }
+
+    #[test]
+    fn test_engine() {
+        let mut cli = Cli::new();
+        run(&mut cli, "new 2k/K2/3/3 b PBRpbr");
+
+        assert!(run(&mut cli, "eval depth 2").starts_with("win in 1 at depth 1: a2a1"));
+        let output = run(&mut cli, "go depth 2");
+        assert!(output.starts_with("engine plays a2a1 (win in 1)"));
+        assert!(output.contains("game over: Black wins"));
+        assert!(cli.command("go", &mut vec![]).is_err());
+    }
+
+    #[test]
+    fn test_save_and_load() {
+        for extension in ["txt", "kif", "csa"] {
+            let mut cli = Cli::new();
+            run(&mut cli, "b3b2");
+            run(&mut cli, "c1b2");
+            cli.record.result = GameResult::WhiteWin;
+            let path = std::env::temp_dir().join(format!(
+                "lewek-cli-{}.{}",
+                std::process::id(),
+                extension
+            ));
+            let path = path.to_str().unwrap();
+
+            run(&mut cli, &format!("save {}", path));
+            let mut loaded = Cli::new();
+            run(&mut loaded, &format!("load {}", path));
+            std::fs::remove_file(path).unwrap();
+
+            assert_eq!(loaded.record.moves(), cli.record.moves(), "{}", extension);
+            assert_eq!(loaded.record.result, GameResult::WhiteWin, "{}", extension);
+        }
+    }
+
+    #[test]
+    fn test_errors() {
+        let mut cli = Cli::new();
+
+        assert_eq!(
+            cli.command("go depth x", &mut vec![]),
+            Err("invalid depth 'x'".to_owned())
+        );
+        assert!(cli.command("new rkb", &mut vec![]).is_err());
+        assert!(cli.command("load /nonexistent/game", &mut vec![]).is_err());
+        assert_eq!(
+            cli.command("fly away", &mut vec![]),
+            Err("unknown command 'fly away', try 'help'".to_owned())
+        );
+        assert_eq!(
+            cli.command("fly", &mut vec![]),
+            Err("unknown command 'fly', try 'help'".to_owned())
+        );
+    }
+}
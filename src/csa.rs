@@ -0,0 +1,687 @@
+use std::fmt;
+use std::str::FromStr;
+
+use crate::game::*;
+use crate::parser::{checked_position, SfenError};
+
+const VERSION: &str = "V2.2";
+
+/// Two-letter code of the piece, the same for both colors.
+pub fn csa_code(kind: PieceKind) -> &'static str {
+    match kind {
+        PieceKind::Pawn => "HI",
+        PieceKind::Bishop => "ZO",
+        PieceKind::Rook => "KI",
+        PieceKind::King => "LI",
+        PieceKind::PromotedPawn => "NI",
+    }
+}
+
+fn parse_code(code: &str) -> Option<PieceKind> {
+    ALL_PIECE_KINDS
+        .into_iter()
+        .find(|&kind| csa_code(kind) == code)
+}
+
+fn sign(color: Color) -> char {
+    match color {
+        Color::Black => '+',
+        Color::White => '-',
+    }
+}
+
+fn parse_sign(sign: u8) -> Option<Color> {
+    match sign {
+        b'+' => Some(Color::Black),
+        b'-' => Some(Color::White),
+        _ => None,
+    }
+}
+
+/// Why a game ended before its final position decided it.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Termination {
+    /// The side to play resigned.
+    Resign,
+    /// The side to play ran out of time.
+    TimeUp,
+    /// The side to play made an illegal move.
+    IllegalMove,
+    Repetition,
+    /// The players agreed to a draw.
+    Draw,
+    /// The game was interrupted and has no result.
+    Interrupt,
+}
+
+impl Termination {
+    pub fn code(&self) -> &'static str {
+        match self {
+            Termination::Resign => "%TORYO",
+            Termination::TimeUp => "%TIME_UP",
+            Termination::IllegalMove => "%ILLEGAL_MOVE",
+            Termination::Repetition => "%SENNICHITE",
+            Termination::Draw => "%HIKIWAKE",
+            Termination::Interrupt => "%CHUDAN",
+        }
+    }
+
+    fn from_code(code: &str) -> Option<Self> {
+        [
+            Termination::Resign,
+            Termination::TimeUp,
+            Termination::IllegalMove,
+            Termination::Repetition,
+            Termination::Draw,
+            Termination::Interrupt,
+        ]
+        .into_iter()
+        .find(|termination| termination.code() == code)
+    }
+}
+
+/// Reason why a CSA record could not be read. Lines are numbered from 1.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CsaError {
+    Syntax { line: usize, expected: &'static str },
+    IllegalPosition { line: usize, error: SfenError },
+    IllegalMove { line: usize, mv: Move },
+}
+
+impl fmt::Display for CsaError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CsaError::Syntax { line, expected } => {
+                write!(f, "invalid record at line {}: expected {}", line, expected)
+            }
+            CsaError::IllegalPosition { line, error } => {
+                write!(f, "invalid starting position at line {}: {}", line, error)
+            }
+            CsaError::IllegalMove { line, mv } => {
+                write!(f, "illegal move {} at line {}", mv, line)
+            }
+        }
+    }
+}
+
+impl std::error::Error for CsaError {}
+
+/// A move together with the number of seconds spent on it.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct CsaMove {
+    pub mv: Move,
+    pub time: Option<u32>,
+}
+
+/// A game record in a CSA dialect for the 3x4 board.
+///
+/// Fields are written as the file `1`-`3` counted from the right
+/// followed by the rank `1`-`4` counted from the top, pieces
+/// by the codes of [`csa_code`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Csa {
+    pub black_name: Option<String>,
+    pub white_name: Option<String>,
+    /// Metadata given as `$NAME:VALUE`, in the order they appear in the record.
+    pub headers: Vec<(String, String)>,
+    pub start: Position,
+    pub moves: Vec<CsaMove>,
+    pub termination: Option<Termination>,
+}
+
+impl Csa {
+    pub fn new(start: Position) -> Self {
+        Csa {
+            black_name: None,
+            white_name: None,
+            headers: vec![],
+            start,
+            moves: vec![],
+            termination: None,
+        }
+    }
+
+    /// Record of the moves played from `start`, without times.
+    pub fn from_moves(start: Position, moves: &[Move]) -> Self {
+        Csa {
+            moves: moves.iter().map(|&mv| CsaMove { mv, time: None }).collect(),
+            ..Csa::new(start)
+        }
+    }
+
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(header, _)| header == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    pub fn set_header(&mut self, name: &str, value: &str) {
+        match self.headers.iter_mut().find(|(header, _)| header == name) {
+            Some((_, old)) => *old = value.to_owned(),
+            None => self.headers.push((name.to_owned(), value.to_owned())),
+        }
+    }
+
+    /// The game after all the moves of the record.
+    pub fn game_state(&self) -> GameState {
+        let mut game = GameState::new(self.start);
+        for csa_move in &self.moves {
+            game.make_move(csa_move.mv);
+        }
+        game
+    }
+
+    pub fn position(&self) -> Position {
+        self.game_state().current_position
+    }
+
+    /// Result given by the termination, or by the final position if there is none.
+    pub fn result(&self) -> GameResult {
+        let game = self.game_state();
+        let to_play = game.current_position.to_play();
+        match self.termination {
+            None => game.result(),
+            Some(Termination::Resign | Termination::TimeUp | Termination::IllegalMove) => {
+                GameResult::win(to_play.opposite())
+            }
+            Some(Termination::Repetition | Termination::Draw) => GameResult::Draw,
+            Some(Termination::Interrupt) => GameResult::Ongoing,
+        }
+    }
+
+    /// Sets the termination so that [`Csa::result`] gives `result`. A win of
+    /// the side to play other than by the final position can't be written in
+    /// CSA, so it leaves the record without a termination.
+    pub fn set_result(&mut self, result: GameResult) {
+        let game = self.game_state();
+        let to_play = game.current_position.to_play();
+        self.termination = match result {
+            result if result == game.result() => None,
+            GameResult::Ongoing => Some(Termination::Interrupt),
+            GameResult::Draw => Some(Termination::Draw),
+            result if result == GameResult::win(to_play.opposite()) => Some(Termination::Resign),
+            _ => None,
+        };
+    }
+}
+
+fn square(field: Field) -> String {
+    let (row, col) = field2rowcol(field);
+    format!("{}{}", COLS - col, row + 1)
+}
+
+fn write_move(position: &Position, mv: Move) -> String {
+    match mv {
+        Move::Board { from, to } => {
+            let piece = position.board()[from].expect("a piece on the starting field");
+            let kind = if position.is_promotion(mv) {
+                PieceKind::PromotedPawn
+            } else {
+                piece.kind
+            };
+            format!(
+                "{}{}{}{}",
+                sign(position.to_play()),
+                square(from),
+                square(to),
+                csa_code(kind)
+            )
+        }
+        Move::Drop { piece, to } => format!(
+            "{}00{}{}",
+            sign(position.to_play()),
+            square(to),
+            csa_code(piece.into())
+        ),
+    }
+}
+
+impl fmt::Display for Csa {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{}", VERSION)?;
+        if let Some(name) = &self.black_name {
+            writeln!(f, "N+{}", name)?;
+        }
+        if let Some(name) = &self.white_name {
+            writeln!(f, "N-{}", name)?;
+        }
+        for (name, value) in &self.headers {
+            writeln!(f, "${}:{}", name, value)?;
+        }
+
+        let mut start = self.start;
+        start.set_move_number(None);
+        if start == Position::startpos() {
+            writeln!(f, "PI")?;
+        } else {
+            for row in 0..ROWS {
+                write!(f, "P{}", row + 1)?;
+                for col in 0..COLS {
+                    match start.board()[rowcol2field(row, col)] {
+                        Some(piece) => write!(f, "{}{}", sign(piece.color), csa_code(piece.kind))?,
+                        None => write!(f, " * ")?,
+                    }
+                }
+                writeln!(f)?;
+            }
+            for color in [Color::Black, Color::White] {
+                let hand = start.hand(color);
+                if !hand.is_empty() {
+                    write!(f, "P{}", sign(color))?;
+                    for (piece, count) in hand.iter() {
+                        for _ in 0..count {
+                            write!(f, "00{}", csa_code(piece.into()))?;
+                        }
+                    }
+                    writeln!(f)?;
+                }
+            }
+        }
+        writeln!(f, "{}", sign(start.to_play()))?;
+
+        let mut position = self.start;
+        for csa_move in &self.moves {
+            writeln!(f, "{}", write_move(&position, csa_move.mv))?;
+            if let Some(time) = csa_move.time {
+                writeln!(f, "T{}", time)?;
+            }
+            position.make_move(csa_move.mv);
+        }
+
+        if let Some(termination) = self.termination {
+            writeln!(f, "{}", termination.code())?;
+        }
+        Ok(())
+    }
+}
+
+fn parse_square(square: &[u8]) -> Option<Field> {
+    match square {
+        &[file @ b'1'..=b'3', rank @ b'1'..=b'4'] => Some(rowcol2field(
+            (rank - b'1') as Row,
+            COLS - (file - b'0') as Col,
+        )),
+        _ => None,
+    }
+}
+
+fn parse_move(statement: &str, position: &Position, line: usize) -> Result<Move, CsaError> {
+    let syntax = |expected| CsaError::Syntax { line, expected };
+    let bytes = statement.as_bytes();
+    if bytes.len() != 7 {
+        return Err(syntax("a move like '+2322HI'"));
+    }
+    if parse_sign(bytes[0]) != Some(position.to_play()) {
+        return Err(syntax("a move of the side to play"));
+    }
+    let to = parse_square(&bytes[3..5]).ok_or(syntax("a destination square"))?;
+    let kind = parse_code(&statement[5..]).ok_or(syntax("a piece code"))?;
+
+    let mv = if &bytes[1..3] == b"00" {
+        let piece = HandPiece::try_from(kind)
+            .ok()
+            .filter(|_| kind != PieceKind::PromotedPawn)
+            .ok_or(syntax("a piece which can be dropped"))?;
+        Move::Drop { piece, to }
+    } else {
+        let from = parse_square(&bytes[1..3]).ok_or(syntax("a starting square"))?;
+        let mv = Move::Board { from, to };
+        let moved = position.board()[from].map(|piece| {
+            if position.is_promotion(mv) {
+                PieceKind::PromotedPawn
+            } else {
+                piece.kind
+            }
+        });
+        if moved != Some(kind) {
+            return Err(syntax("the code of the piece after the move"));
+        }
+        mv
+    };
+
+    if !position.legal_moves().contains(&mv) {
+        return Err(CsaError::IllegalMove { line, mv });
+    }
+    Ok(mv)
+}
+
+/// Parses the cells of a board row like `-KI-LI-ZO`.
+fn parse_row(cells: &str) -> Option<[Option<Piece>; COLS]> {
+    let mut row = [None; COLS];
+    let mut rest = cells;
+    for cell in row.iter_mut() {
+        let end = rest.len().min(3);
+        let text = rest.get(..end)?;
+        rest = &rest[end..];
+        if text.trim() == "*" {
+            continue;
+        }
+        let color = parse_sign(*text.as_bytes().first()?)?;
+        let kind = parse_code(text.get(1..)?)?;
+        *cell = Some(Piece { kind, color });
+    }
+    rest.trim().is_empty().then_some(row)
+}
+
+impl FromStr for Csa {
+    type Err = CsaError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut csa = Csa::new(Position::startpos());
+        let mut board = None;
+        let mut hands = [Hand::empty(), Hand::empty()];
+        let mut game: Option<GameState> = None;
+
+        for (index, text) in s.lines().enumerate() {
+            let line = index + 1;
+            let syntax = |expected| CsaError::Syntax { line, expected };
+            if text.starts_with('\'') {
+                continue;
+            }
+
+            for statement in text.trim_end().split(',') {
+                if statement.is_empty() || statement.starts_with('V') {
+                    continue;
+                }
+
+                if let Some(game) = game.as_mut() {
+                    if let Some(time) = statement.strip_prefix('T') {
+                        let csa_move = csa
+                            .moves
+                            .last_mut()
+                            .ok_or(syntax("a move before its time"))?;
+                        csa_move.time =
+                            Some(time.parse().map_err(|_| syntax("a time in seconds"))?);
+                    } else if statement.starts_with('%') {
+                        if csa.termination.is_some() {
+                            return Err(syntax("a single termination"));
+                        }
+                        csa.termination =
+                            Some(Termination::from_code(statement).ok_or(syntax("a termination"))?);
+                    } else {
+                        if csa.termination.is_some() || game.result() != GameResult::Ongoing {
+                            return Err(syntax("no moves after the end of the game"));
+                        }
+                        let mv = parse_move(statement, &game.current_position, line)?;
+                        game.make_move(mv);
+                        csa.moves.push(CsaMove { mv, time: None });
+                    }
+                    continue;
+                }
+
+                if let Some(name) = statement.strip_prefix("N+") {
+                    csa.black_name = Some(name.to_owned());
+                } else if let Some(name) = statement.strip_prefix("N-") {
+                    csa.white_name = Some(name.to_owned());
+                } else if let Some(header) = statement.strip_prefix('$') {
+                    let (name, value) = header.split_once(':').ok_or(syntax("'$NAME:VALUE'"))?;
+                    csa.set_header(name, value);
+                } else if statement == "PI" {
+                    board = Some(*Position::startpos().board());
+                } else if let Some(pieces) = statement
+                    .strip_prefix("P+")
+                    .map(|pieces| (Color::Black, pieces))
+                    .or_else(|| {
+                        statement
+                            .strip_prefix("P-")
+                            .map(|pieces| (Color::White, pieces))
+                    })
+                {
+                    let (color, pieces) = pieces;
+                    if pieces.len() % 4 != 0 {
+                        return Err(syntax("hand pieces like '00HI'"));
+                    }
+                    for piece in pieces.as_bytes().chunks(4) {
+                        let piece = std::str::from_utf8(piece)
+                            .ok()
+                            .and_then(|piece| piece.strip_prefix("00"))
+                            .and_then(parse_code)
+                            .filter(|&kind| kind != PieceKind::PromotedPawn)
+                            .and_then(|kind| HandPiece::try_from(kind).ok())
+                            .ok_or(syntax("hand pieces like '00HI'"))?;
+                        if !hands[color as usize].add(piece) {
+                            return Err(syntax("at most two pieces of a kind in hand"));
+                        }
+                    }
+                } else if let Some(row) = statement
+                    .strip_prefix('P')
+                    .and_then(|rest| rest.get(..1))
+                    .and_then(|row| row.parse::<usize>().ok())
+                {
+                    if !(1..=ROWS).contains(&row) {
+                        return Err(syntax("a row from P1 to P4"));
+                    }
+                    let cells =
+                        parse_row(&statement[2..]).ok_or(syntax("three cells like '-KI'"))?;
+                    let board = board.get_or_insert_with(Board::empty);
+                    for (col, cell) in cells.into_iter().enumerate() {
+                        board[rowcol2field(row - 1, col)] = cell;
+                    }
+                } else if let Some(to_play) = match statement {
+                    "+" => Some(Color::Black),
+                    "-" => Some(Color::White),
+                    _ => None,
+                } {
+                    let board = board.ok_or(syntax("a position before the side to play"))?;
+                    let [black_hand, white_hand] = hands;
+                    csa.start = checked_position(board, to_play, black_hand, white_hand)
+                        .map_err(|error| CsaError::IllegalPosition { line, error })?;
+                    game = Some(GameState::new(csa.start));
+                } else {
+                    return Err(syntax("a header, a position or the side to play"));
+                }
+            }
+        }
+
+        if game.is_none() {
+            return Err(CsaError::Syntax {
+                line: s.lines().count(),
+                expected: "the side to play",
+            });
+        }
+        Ok(csa)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse_sfen;
+
+    fn moves(position: &Position, notation: &[&str]) -> Vec<Move> {
+        let mut position = *position;
+        notation
+            .iter()
+            .map(|text| {
+                let mv = position.parse_move(text).unwrap();
+                position.make_move(mv);
+                mv
+            })
+            .collect()
+    }
+
+    const GAME: &str = "\
+V2.2
+N+Alice
+N-Bob
+$EVENT:Club championship
+PI
++
++2322HI
+T5
+-1122ZO
+T3
++0012HI
+T10
+-2213ZO
+T1
++1211NI
+T2
+-1324ZO
+T4
+";
+
+    fn game() -> Csa {
+        let start = Position::startpos();
+        let played = moves(&start, &["b3b2", "c1b2", "P*c2", "b2c3", "c2c1+", "c3b4"]);
+        let times = [5, 3, 10, 1, 2, 4];
+
+        let mut csa = Csa::new(start);
+        csa.moves = played
+            .into_iter()
+            .zip(times)
+            .map(|(mv, time)| CsaMove {
+                mv,
+                time: Some(time),
+            })
+            .collect();
+        csa.black_name = Some("Alice".to_owned());
+        csa.white_name = Some("Bob".to_owned());
+        csa.set_header("EVENT", "Club championship");
+        csa
+    }
+
+    #[test]
+    fn test_write_game() {
+        assert_eq!(game().to_string(), GAME);
+        assert_eq!(game().result(), GameResult::WhiteWin);
+    }
+
+    #[test]
+    fn test_read_game() {
+        let csa: Csa = GAME.parse().unwrap();
+
+        assert_eq!(csa, game());
+        assert_eq!(csa.header("EVENT"), Some("Club championship"));
+    }
+
+    #[test]
+    fn test_custom_start() {
+        let start = parse_sfen("1k1/P2/3/1K1 b PBbr2").unwrap();
+        let csa = Csa::from_moves(start, &moves(&start, &["a2a1+", "b1a1"]));
+        let text = csa.to_string();
+
+        assert_eq!(
+            text,
+            "\
+V2.2
+P1 * -LI * 
+P2+HI *  * 
+P3 *  *  * 
+P4 * +LI * 
+P+00HI00ZO
+P-00ZO00KI00KI
++
++3231NI
+-2131LI
+"
+        );
+        assert_eq!(text.parse::<Csa>(), Ok(csa));
+    }
+
+    #[test]
+    fn test_set_result() {
+        let start = Position::startpos();
+        let mut csa = Csa::from_moves(start, &moves(&start, &["b3b2", "c1b2"]));
+        let cases = [
+            (GameResult::WhiteWin, Some(Termination::Resign)),
+            (GameResult::Draw, Some(Termination::Draw)),
+            (GameResult::Ongoing, None),
+            (GameResult::BlackWin, None),
+        ];
+
+        for (result, termination) in cases {
+            csa.set_result(result);
+            assert_eq!(csa.termination, termination, "{:?}", result);
+        }
+    }
+
+    #[test]
+    fn test_terminations() {
+        let start = Position::startpos();
+        let played = moves(&start, &["b3b2", "c1b2"]);
+        let cases = [
+            (Termination::Resign, GameResult::WhiteWin),
+            (Termination::TimeUp, GameResult::WhiteWin),
+            (Termination::IllegalMove, GameResult::WhiteWin),
+            (Termination::Repetition, GameResult::Draw),
+            (Termination::Draw, GameResult::Draw),
+            (Termination::Interrupt, GameResult::Ongoing),
+        ];
+
+        for (termination, result) in cases {
+            let csa = Csa {
+                termination: Some(termination),
+                ..Csa::from_moves(start, &played)
+            };
+            let text = csa.to_string();
+
+            assert!(text.ends_with(&format!("{}\n", termination.code())));
+            assert_eq!(csa.result(), result);
+            assert_eq!(text.parse::<Csa>(), Ok(csa));
+        }
+    }
+
+    #[test]
+    fn test_statements_separated_by_commas() {
+        let csa: Csa = "' a comment\nPI\n+\n+2322HI,T5,-1122ZO\n%TORYO\n"
+            .parse()
+            .unwrap();
+
+        assert_eq!(csa.moves.len(), 2);
+        assert_eq!(csa.moves[0].time, Some(5));
+        assert_eq!(csa.termination, Some(Termination::Resign));
+        assert_eq!(csa.result(), GameResult::WhiteWin);
+    }
+
+    #[test]
+    fn test_errors() {
+        let cases = [
+            (
+                "PI\n+\n+2322ZO\n",
+                3,
+                "the code of the piece after the move",
+            ),
+            ("PI\n+\n-2322HI\n", 3, "a move of the side to play"),
+            ("PI\n+\n+2322\n", 3, "a move like '+2322HI'"),
+            ("PI\n+\n+0022LI\n", 3, "a piece which can be dropped"),
+            ("PI\n+\n%RESIGN\n", 3, "a termination"),
+            ("PI\n+\nT5\n", 3, "a move before its time"),
+            ("+\n", 1, "a position before the side to play"),
+            ("P5 *  *  * \n", 1, "a row from P1 to P4"),
+            ("P1-XX *  * \n", 1, "three cells like '-KI'"),
+            ("P1abé\n", 1, "three cells like '-KI'"),
+            ("P+00LI\n", 1, "hand pieces like '00HI'"),
+            ("P+00NI\n", 1, "hand pieces like '00HI'"),
+            (
+                "P+00HI00HI00HI\n",
+                1,
+                "at most two pieces of a kind in hand",
+            ),
+            ("PI\n", 1, "the side to play"),
+            ("hello\n", 1, "a header, a position or the side to play"),
+        ];
+
+        for (text, line, expected) in cases {
+            assert_eq!(
+                text.parse::<Csa>(),
+                Err(CsaError::Syntax { line, expected }),
+                "{}",
+                text
+            );
+        }
+
+        assert_eq!(
+            "PI\n+\n+2421LI\n".parse::<Csa>(),
+            Err(CsaError::IllegalMove {
+                line: 3,
+                mv: Move::Board { from: 10, to: 1 }
+            })
+        );
+        assert!(matches!(
+            "P1-LI-LI * \nP4 * +LI * \n-\n".parse::<Csa>(),
+            Err(CsaError::IllegalPosition { line: 3, .. })
+        ));
+    }
+}
@@ -0,0 +1,339 @@
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+use crate::game::*;
+
+/// Weights of the evaluation terms, in hundredths of a chick.
+///
+/// They can be saved to and loaded from a simple text format with one
+/// `name = value` pair per line, so that they can be tuned without
+/// recompiling. Lines starting with `#` are comments and missing
+/// weights keep their default values.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Weights {
+    pub pawn: i32,
+    pub bishop: i32,
+    pub rook: i32,
+    pub promoted_pawn: i32,
+    pub hand_pawn: i32,
+    pub hand_bishop: i32,
+    pub hand_rook: i32,
+    /// Bonus for each field the lion can safely move to.
+    pub lion_mobility: i32,
+    /// Penalty for each field next to the lion controlled by the opponent.
+    pub lion_danger: i32,
+    /// Bonus for each row a chick has advanced.
+    pub pawn_advance: i32,
+    /// Bonus for each row the lion has advanced.
+    pub lion_advance: i32,
+    /// Bonus for a lion which can safely step onto the opponent's back row.
+    pub try_threat: i32,
+}
+
+impl Default for Weights {
+    fn default() -> Self {
+        Weights {
+            pawn: 100,
+            bishop: 300,
+            rook: 400,
+            promoted_pawn: 450,
+            hand_pawn: 120,
+            hand_bishop: 350,
+            hand_rook: 450,
+            lion_mobility: 10,
+            lion_danger: 25,
+            pawn_advance: 10,
+            lion_advance: 15,
+            try_threat: 600,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum WeightsError {
+    Io(std::io::Error),
+    /// A line which is not a `name = value` pair of a known weight.
+    Syntax {
+        line: usize,
+        text: String,
+    },
+}
+
+impl fmt::Display for WeightsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            WeightsError::Io(error) => write!(f, "cannot read weights: {}", error),
+            WeightsError::Syntax { line, text } => {
+                write!(f, "invalid weight on line {}: '{}'", line, text)
+            }
+        }
+    }
+}
+
+impl std::error::Error for WeightsError {}
+
+impl From<std::io::Error> for WeightsError {
+    fn from(error: std::io::Error) -> Self {
+        WeightsError::Io(error)
+    }
+}
+
+impl Weights {
+    fn fields_mut(&mut self) -> [(&'static str, &mut i32); 12] {
+        [
+            ("pawn", &mut self.pawn),
+            ("bishop", &mut self.bishop),
+            ("rook", &mut self.rook),
+            ("promoted_pawn", &mut self.promoted_pawn),
+            ("hand_pawn", &mut self.hand_pawn),
+            ("hand_bishop", &mut self.hand_bishop),
+            ("hand_rook", &mut self.hand_rook),
+            ("lion_mobility", &mut self.lion_mobility),
+            ("lion_danger", &mut self.lion_danger),
+            ("pawn_advance", &mut self.pawn_advance),
+            ("lion_advance", &mut self.lion_advance),
+            ("try_threat", &mut self.try_threat),
+        ]
+    }
+
+    pub fn parse(text: &str) -> Result<Self, WeightsError> {
+        let mut weights = Weights::default();
+
+        for (index, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let error = || WeightsError::Syntax {
+                line: index + 1,
+                text: line.to_owned(),
+            };
+
+            let (name, value) = line.split_once('=').ok_or_else(error)?;
+            let value: i32 = value.trim().parse().map_err(|_| error())?;
+            let mut fields = weights.fields_mut();
+            let (_, field) = fields
+                .iter_mut()
+                .find(|(field_name, _)| *field_name == name.trim())
+                .ok_or_else(error)?;
+            **field = value;
+        }
+
+        Ok(weights)
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, WeightsError> {
+        Weights::parse(&fs::read_to_string(path)?)
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), WeightsError> {
+        fs::write(path, self.to_string())?;
+        Ok(())
+    }
+
+    pub fn piece(&self, kind: PieceKind) -> i32 {
+        match kind {
+            PieceKind::Pawn => self.pawn,
+            PieceKind::Bishop => self.bishop,
+            PieceKind::Rook => self.rook,
+            PieceKind::PromotedPawn => self.promoted_pawn,
+            PieceKind::King => 0,
+        }
+    }
+
+    pub fn hand_piece(&self, piece: HandPiece) -> i32 {
+        match piece {
+            HandPiece::Pawn => self.hand_pawn,
+            HandPiece::Bishop => self.hand_bishop,
+            HandPiece::Rook => self.hand_rook,
+        }
+    }
+}
+
+impl fmt::Display for Weights {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut weights = *self;
+        for (name, value) in weights.fields_mut() {
+            writeln!(f, "{} = {}", name, value)?;
+        }
+        Ok(())
+    }
+}
+
+/// Number of rows a piece of the given color has advanced from its own back row.
+fn advancement(field: Field, color: Color) -> i32 {
+    let (row, _) = field2rowcol(field);
+    match color {
+        Color::Black => (ROWS - 1 - row) as i32,
+        Color::White => row as i32,
+    }
+}
+
+fn side_score(position: &Position, color: Color, weights: &Weights) -> i32 {
+    let bitboard = position.bitboard();
+    let opponent = color.opposite();
+    let mut score = 0;
+
+    for field in fields(bitboard.color(color)) {
+        let piece = position.board()[field].expect("a piece of the color");
+        score += weights.piece(piece.kind);
+        if piece.kind == PieceKind::Pawn {
+            score += weights.pawn_advance * advancement(field, color);
+        }
+    }
+
+    for (piece, count) in position.hand(color).iter() {
+        score += weights.hand_piece(piece) * count as i32;
+    }
+
+    if let Some(lion) = position.king_field(color) {
+        let king = Piece {
+            kind: PieceKind::King,
+            color,
+        };
+        let mut safe_moves = 0;
+        let mut danger = 0;
+        let mut try_available = false;
+
+        for to in fields(attacks(lion, king)) {
+            let attacked = bitboard.attackers(to, opponent) != 0;
+            if attacked {
+                danger += 1;
+            } else if bitboard.color(color) & (1 << to) == 0 {
+                safe_moves += 1;
+                let (row, _) = field2rowcol(to);
+                try_available |= row == promotion_row(color);
+            }
+        }
+
+        score += weights.lion_mobility * safe_moves;
+        score -= weights.lion_danger * danger;
+        score += weights.lion_advance * advancement(lion, color);
+        if try_available {
+            score += weights.try_threat;
+        }
+    }
+
+    score
+}
+
+/// Static score of the position from the point of view of the side to play.
+pub fn evaluate_with(position: &Position, weights: &Weights) -> i32 {
+    side_score(position, position.to_play(), weights)
+        - side_score(position, position.to_play().opposite(), weights)
+}
+
+/// Static score of the position with the default weights.
+pub fn evaluate(position: &Position) -> i32 {
+    evaluate_with(position, &Weights::default())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse_sfen;
+
+    #[test]
+    fn test_startpos_is_balanced() {
+        assert_eq!(evaluate(&Position::startpos()), 0);
+
+        let mut position = Position::startpos();
+        position.set_to_play(Color::White);
+        assert_eq!(evaluate(&position), 0);
+    }
+
+    #[test]
+    fn test_score_is_from_side_to_play() {
+        let black = parse_sfen("1k1/3/1P1/1K1 b PBRbr").unwrap();
+        let white = parse_sfen("1k1/3/1P1/1K1 w PBRbr").unwrap();
+
+        assert!(evaluate(&black) > 0);
+        assert_eq!(evaluate(&black), -evaluate(&white));
+    }
+
+    #[test]
+    fn test_hand_pieces_worth_more() {
+        let on_board = parse_sfen("1k1/3/R2/1K1 b PBpbr").unwrap();
+        let in_hand = parse_sfen("1k1/3/3/1K1 b PBRpbr").unwrap();
+
+        assert!(evaluate(&in_hand) > evaluate(&on_board));
+    }
+
+    #[test]
+    fn test_pawn_advancement() {
+        let back = parse_sfen("1k1/3/3/PK1 b BRpbr").unwrap();
+        let forward = parse_sfen("1k1/3/P2/1K1 b BRpbr").unwrap();
+
+        assert!(evaluate(&forward) > evaluate(&back));
+    }
+
+    #[test]
+    fn test_try_threat() {
+        let threat = parse_sfen("2k/K2/3/3 b PBRpbr").unwrap();
+        let no_threat = parse_sfen("2k/3/3/K2 b PBRpbr").unwrap();
+
+        assert!(evaluate(&threat) - evaluate(&no_threat) >= Weights::default().try_threat);
+    }
+
+    #[test]
+    fn test_lion_danger() {
+        let safe = parse_sfen("1k1/3/3/1K1 b PBRpbr").unwrap();
+        let threatened = parse_sfen("1k1/3/r2/1K1 b PBRpb").unwrap();
+        let weights = Weights {
+            rook: 0,
+            hand_rook: 0,
+            ..Weights::default()
+        };
+
+        assert!(evaluate_with(&threatened, &weights) < evaluate_with(&safe, &weights));
+    }
+
+    #[test]
+    fn test_parse_weights() {
+        let text = "# tuned\npawn = 90\n\n  try_threat=1000  \n";
+
+        let weights = Weights::parse(text).unwrap();
+
+        assert_eq!(weights.pawn, 90);
+        assert_eq!(weights.try_threat, 1000);
+        assert_eq!(weights.rook, Weights::default().rook);
+    }
+
+    #[test]
+    fn test_parse_weights_errors() {
+        let inputs = ["pawn 90", "pawn = x", "queen = 900"];
+
+        for text in inputs {
+            let result = Weights::parse(text);
+            assert!(
+                matches!(result, Err(WeightsError::Syntax { line: 1, .. })),
+                "{}",
+                text
+            );
+        }
+    }
+
+    #[test]
+    fn test_weights_file_roundtrip() {
+        let weights = Weights {
+            hand_bishop: 333,
+            lion_danger: -4,
+            ..Weights::default()
+        };
+        let path = std::env::temp_dir().join(format!("lewek-weights-{}.txt", std::process::id()));
+
+        weights.save(&path).unwrap();
+        let loaded = Weights::load(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(loaded, weights);
+    }
+
+    #[test]
+    fn test_load_missing_file() {
+        let result = Weights::load("/nonexistent/lewek/weights.txt");
+
+        assert!(matches!(result, Err(WeightsError::Io(_))));
+    }
+}
@@ -0,0 +1,231 @@
+use crate::game::*;
+
+/// Set of fields, with bit `n` standing for field `n`.
+pub type Mask = u16;
+
+pub const FULL_BOARD: Mask = (1 << BOARD_SIZE) - 1;
+
+pub const ALL_PIECE_KINDS: [PieceKind; 5] = [
+    PieceKind::Pawn,
+    PieceKind::Bishop,
+    PieceKind::Rook,
+    PieceKind::King,
+    PieceKind::PromotedPawn,
+];
+
+const fn attacks_from(field: Field, kind: PieceKind, color: Color) -> Mask {
+    let steps = kind.steps();
+    let row = (field / COLS) as isize;
+    let col = (field % COLS) as isize;
+
+    let mut attacks = 0;
+    let mut i = 0;
+    while i < steps.len() {
+        let (mut drow, mut dcol) = steps[i];
+        if let Color::White = color {
+            drow = -drow;
+            dcol = -dcol;
+        }
+        let (to_row, to_col) = (row + drow, col + dcol);
+        if to_row >= 0 && to_row < ROWS as isize && to_col >= 0 && to_col < COLS as isize {
+            attacks |= 1 << (to_row as usize * COLS + to_col as usize);
+        }
+        i += 1;
+    }
+    attacks
+}
+
+const fn generate_attacks() -> [[[Mask; BOARD_SIZE]; ALL_PIECE_KINDS.len()]; 2] {
+    let mut attacks = [[[0; BOARD_SIZE]; ALL_PIECE_KINDS.len()]; 2];
+    let colors = [Color::Black, Color::White];
+
+    let mut color = 0;
+    while color < colors.len() {
+        let mut kind = 0;
+        while kind < ALL_PIECE_KINDS.len() {
+            let mut field = 0;
+            while field < BOARD_SIZE {
+                attacks[color][kind][field] =
+                    attacks_from(field, ALL_PIECE_KINDS[kind], colors[color]);
+                field += 1;
+            }
+            kind += 1;
+        }
+        color += 1;
+    }
+    attacks
+}
+
+static ATTACKS: [[[Mask; BOARD_SIZE]; ALL_PIECE_KINDS.len()]; 2] = generate_attacks();
+
+/// Fields a piece standing on `field` can move to, ignoring other pieces.
+pub fn attacks(field: Field, piece: Piece) -> Mask {
+    ATTACKS[piece.color as usize][piece.kind as usize][field]
+}
+
+/// Iterates over the fields of a bitboard, from the lowest.
+pub fn fields(mut bitboard: Mask) -> impl Iterator<Item = Field> {
+    std::iter::from_fn(move || {
+        if bitboard == 0 {
+            return None;
+        }
+        let field = bitboard.trailing_zeros() as Field;
+        bitboard &= bitboard - 1;
+        Some(field)
+    })
+}
+
+/// Board stored as one bitboard per piece kind and color.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub struct BitBoard {
+    pieces: [[Mask; ALL_PIECE_KINDS.len()]; 2],
+    colors: [Mask; 2],
+}
+
+impl BitBoard {
+    pub fn empty() -> Self {
+        BitBoard::default()
+    }
+
+    pub fn pieces(&self, piece: Piece) -> Mask {
+        self.pieces[piece.color as usize][piece.kind as usize]
+    }
+
+    pub fn color(&self, color: Color) -> Mask {
+        self.colors[color as usize]
+    }
+
+    pub fn occupied(&self) -> Mask {
+        self.colors[0] | self.colors[1]
+    }
+
+    /// Piece standing on the field, if any.
+    pub fn get(&self, field: Field) -> Option<Piece> {
+        ALL_PIECES
+            .into_iter()
+            .find(|&piece| self.pieces(piece) & 1 << field != 0)
+    }
+
+    pub fn put(&mut self, field: Field, piece: Piece) {
+        let bit = 1 << field;
+        self.pieces[piece.color as usize][piece.kind as usize] |= bit;
+        self.colors[piece.color as usize] |= bit;
+    }
+
+    pub fn take(&mut self, field: Field) -> Option<Piece> {
+        let piece = self.get(field)?;
+        let bit = 1 << field;
+        self.pieces[piece.color as usize][piece.kind as usize] &= !bit;
+        self.colors[piece.color as usize] &= !bit;
+        Some(piece)
+    }
+
+    /// Pieces of color `by` which can move to `field`.
+    ///
+    /// A piece attacks `field` exactly when the same piece of the opposite
+    /// color standing on `field` would attack it, which lets us use a single
+    /// lookup per piece kind.
+    pub fn attackers(&self, field: Field, by: Color) -> Mask {
+        let defender = by.opposite();
+        ALL_PIECE_KINDS.iter().fold(0, |result, &kind| {
+            let piece = Piece { kind, color: by };
+            let reverse = Piece {
+                kind,
+                color: defender,
+            };
+            result | (attacks(field, reverse) & self.pieces(piece))
+        })
+    }
+}
+
+impl From<&Board> for BitBoard {
+    fn from(board: &Board) -> Self {
+        let mut bitboard = BitBoard::empty();
+        for field in ALL_INDEXES {
+            if let Some(piece) = board[field] {
+                bitboard.put(field, piece);
+            }
+        }
+        bitboard
+    }
+}
+
+impl From<&BitBoard> for Board {
+    fn from(bitboard: &BitBoard) -> Self {
+        Board(ALL_INDEXES.map(|field| bitboard.get(field)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mask_of(fields: &[Field]) -> Mask {
+        fields.iter().fold(0, |result, field| result | 1 << field)
+    }
+
+    #[test]
+    fn test_attack_tables_match_steps() {
+        for piece in ALL_PIECES {
+            for field in ALL_INDEXES {
+                let expected: Vec<Field> = piece
+                    .steps()
+                    .filter_map(|step| step_field(field, step))
+                    .collect();
+                assert_eq!(attacks(field, piece), mask_of(&expected), "{:?}", piece);
+            }
+        }
+    }
+
+    #[test]
+    fn test_attacks() {
+        assert_eq!(attacks(4, BLACK_PAWN), mask_of(&[1]));
+        assert_eq!(attacks(4, WHITE_PAWN), mask_of(&[7]));
+        assert_eq!(attacks(0, BLACK_KING), mask_of(&[1, 3, 4]));
+        assert_eq!(attacks(10, WHITE_PROMOTED_PAWN), mask_of(&[7, 9, 11]));
+    }
+
+    #[test]
+    fn test_fields() {
+        let result: Vec<Field> = fields(mask_of(&[0, 5, 11])).collect();
+
+        assert_eq!(result, vec![0, 5, 11]);
+    }
+
+    #[test]
+    fn test_board_conversions() {
+        let board = *Position::startpos().board();
+
+        let bitboard = BitBoard::from(&board);
+
+        assert_eq!(Board::from(&bitboard), board);
+        assert_eq!(bitboard.pieces(BLACK_KING), mask_of(&[10]));
+        assert_eq!(bitboard.color(Color::White), mask_of(&[0, 1, 2, 4]));
+        assert_eq!(bitboard.occupied(), mask_of(&[0, 1, 2, 4, 7, 9, 10, 11]));
+        for field in ALL_INDEXES {
+            assert_eq!(bitboard.get(field), board[field]);
+        }
+    }
+
+    #[test]
+    fn test_put_and_take() {
+        let mut bitboard = BitBoard::empty();
+
+        bitboard.put(3, WHITE_ROOK);
+        assert_eq!(bitboard.pieces(WHITE_ROOK), mask_of(&[3]));
+
+        assert_eq!(bitboard.take(3), Some(WHITE_ROOK));
+        assert_eq!(bitboard.take(3), None);
+        assert_eq!(bitboard, BitBoard::empty());
+    }
+
+    #[test]
+    fn test_attackers() {
+        let bitboard = BitBoard::from(Position::startpos().board());
+
+        assert_eq!(bitboard.attackers(4, Color::Black), mask_of(&[7]));
+        assert_eq!(bitboard.attackers(7, Color::White), mask_of(&[4]));
+        assert_eq!(bitboard.attackers(3, Color::White), mask_of(&[0, 1]));
+        assert_eq!(bitboard.attackers(8, Color::Black), mask_of(&[10, 11]));
+    }
+}
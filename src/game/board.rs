@@ -15,8 +15,22 @@ pub fn rowcol2field(row: Row, col: Col) -> Field {
     COLS * row + col
 }
 
+pub fn field2rowcol(field: Field) -> (Row, Col) {
+    (field / COLS, field % COLS)
+}
+
+/// Field reached from `field` after making `step`, if it is still on the board.
+pub fn step_field(field: Field, step: Step) -> Option<Field> {
+    let (row, col) = field2rowcol(field);
+    let (drow, dcol) = step;
+    let row = row.checked_add_signed(drow).filter(|&row| row < ROWS)?;
+    let col = col.checked_add_signed(dcol).filter(|&col| col < COLS)?;
+    Some(rowcol2field(row, col))
+}
+
 pub const ALL_INDEXES: [Field; BOARD_SIZE] = [0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11];
 
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
 pub struct Board(pub [Option<Piece>; BOARD_SIZE]);
 
 impl Board {
@@ -40,28 +54,216 @@ impl IndexMut<usize> for Board {
     }
 }
 
-#[derive(Debug, PartialEq, Eq)]
-pub struct Hand(pub HashMap<HandPiece, usize>);
+/// Pieces held in hand, stored as a count for each kind of piece.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub struct Hand([u8; ALL_HAND_PIECES.len()]);
+
+impl Hand {
+    pub fn empty() -> Self {
+        Hand::default()
+    }
+
+    pub fn count(&self, piece: HandPiece) -> usize {
+        self.0[piece as usize] as usize
+    }
+
+    /// Sets the count of a piece, clamped to `MAX_HAND_COUNT`.
+    pub fn set(&mut self, piece: HandPiece, count: usize) {
+        self.0[piece as usize] = count.min(MAX_HAND_COUNT) as u8;
+    }
+
+    /// Adds a piece to the hand, returning whether there was room for it.
+    pub fn add(&mut self, piece: HandPiece) -> bool {
+        let count = &mut self.0[piece as usize];
+        if *count as usize == MAX_HAND_COUNT {
+            return false;
+        }
+        *count += 1;
+        true
+    }
+
+    /// Takes a piece out of the hand, returning whether there was one.
+    pub fn remove(&mut self, piece: HandPiece) -> bool {
+        let count = &mut self.0[piece as usize];
+        if *count == 0 {
+            return false;
+        }
+        *count -= 1;
+        true
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.iter().all(|&count| count == 0)
+    }
+
+    /// Pieces held in hand with their counts, skipping pieces which are not held.
+    pub fn iter(&self) -> impl Iterator<Item = (HandPiece, usize)> + '_ {
+        ALL_HAND_PIECES
+            .into_iter()
+            .map(|piece| (piece, self.count(piece)))
+            .filter(|&(_, count)| count > 0)
+    }
+}
+
+impl<const N: usize> From<[(HandPiece, usize); N]> for Hand {
+    fn from(counts: [(HandPiece, usize); N]) -> Self {
+        let mut hand = Hand::empty();
+        for (piece, count) in counts {
+            hand.set(piece, count);
+        }
+        hand
+    }
+}
 
+/// A position with the side to play.
+///
+/// The fields are private, as the Zobrist key and the bitboards used for
+/// move generation are kept next to them and the setters have to update
+/// them together.
+#[derive(Debug, Copy, Clone, Eq)]
 pub struct Position {
-    pub board: Board,
-    pub to_play: Color,
-    pub black_hand: Hand,
-    pub white_hand: Hand,
+    pub(super) board: Board,
+    pub(super) to_play: Color,
+    pub(super) black_hand: Hand,
+    pub(super) white_hand: Hand,
+    /// Number of the current ply, counting from 1,
+    /// if it was given in the SFEN the position was created from.
+    pub(super) move_number: Option<u32>,
+    pub(super) key: u64,
+    /// Copy of `board` used for fast move generation.
+    pub(super) bitboard: BitBoard,
+}
+
+impl Position {
+    pub fn new(board: Board, to_play: Color, black_hand: Hand, white_hand: Hand) -> Self {
+        let mut position = Position {
+            board,
+            to_play,
+            black_hand,
+            white_hand,
+            move_number: None,
+            key: 0,
+            bitboard: BitBoard::from(&board),
+        };
+        position.key = position.compute_key();
+        position
+    }
+
+    pub fn with_move_number(mut self, move_number: u32) -> Self {
+        self.move_number = Some(move_number);
+        self
+    }
+
+    /// The starting position of the game, with Black to play.
+    pub fn startpos() -> Self {
+        let board = Board([
+            Some(WHITE_ROOK),
+            Some(WHITE_KING),
+            Some(WHITE_BISHOP),
+            //-----
+            None,
+            Some(WHITE_PAWN),
+            None,
+            //-----
+            None,
+            Some(BLACK_PAWN),
+            None,
+            // ----
+            Some(BLACK_BISHOP),
+            Some(BLACK_KING),
+            Some(BLACK_ROOK),
+        ]);
+        Position::new(board, Color::Black, Hand::empty(), Hand::empty())
+    }
+
+    #[deprecated(note = "the key is kept up to date by the setters")]
+    pub fn refresh_key(&mut self) {}
+
+    pub fn board(&self) -> &Board {
+        &self.board
+    }
+
+    pub fn bitboard(&self) -> &BitBoard {
+        &self.bitboard
+    }
+
+    pub fn to_play(&self) -> Color {
+        self.to_play
+    }
+
+    /// Number of the current ply, counting from 1,
+    /// if it was given in the SFEN the position was created from.
+    pub fn move_number(&self) -> Option<u32> {
+        self.move_number
+    }
+
+    pub fn hand(&self, color: Color) -> &Hand {
+        match color {
+            Color::Black => &self.black_hand,
+            Color::White => &self.white_hand,
+        }
+    }
+
+    pub(super) fn hand_mut(&mut self, color: Color) -> &mut Hand {
+        match color {
+            Color::Black => &mut self.black_hand,
+            Color::White => &mut self.white_hand,
+        }
+    }
+
+    /// Puts a piece on the field, or empties it.
+    pub fn set_piece(&mut self, field: Field, piece: Option<Piece>) {
+        self.take_piece(field);
+        if let Some(piece) = piece {
+            self.put_piece(field, piece);
+        }
+    }
+
+    pub fn set_hand(&mut self, color: Color, hand: Hand) {
+        for piece in ALL_HAND_PIECES {
+            while self.remove_from_hand(color, piece) {}
+        }
+        for (piece, count) in hand.iter() {
+            for _ in 0..count {
+                self.add_to_hand(color, piece);
+            }
+        }
+    }
+
+    pub fn set_to_play(&mut self, color: Color) {
+        self.key ^= to_play_key(self.to_play) ^ to_play_key(color);
+        self.to_play = color;
+    }
+
+    pub fn set_move_number(&mut self, move_number: Option<u32>) {
+        self.move_number = move_number;
+    }
+}
+
+impl PartialEq for Position {
+    fn eq(&self, other: &Self) -> bool {
+        self.board == other.board
+            && self.to_play == other.to_play
+            && self.black_hand == other.black_hand
+            && self.white_hand == other.white_hand
+            && self.move_number == other.move_number
+    }
 }
 
 /// In order to be able to detect draws by repetition
-/// we hold a history of positions.
-///
-/// This can be optimized by keeping hashes or sfens instead.
+/// we hold the Zobrist keys of previous positions,
+/// together with the number of times each of them occurred.
 pub struct GameState {
     pub current_position: Position,
-    pub previous_positions: Vec<Position>,
+    pub previous_keys: Vec<u64>,
+    pub key_counts: HashMap<u64, usize>,
+    /// Number of occurrences of a position which makes the game a draw.
+    pub repetition_limit: usize,
 }
 
 #[cfg(test)]
 mod tests {
-    use super::rowcol2field;
+    use super::*;
 
     #[test]
     pub fn test_rowcol_conversion() {
@@ -85,4 +287,86 @@ mod tests {
             .collect();
         assert_eq!(results, (0..12).collect::<Vec<usize>>())
     }
+
+    #[test]
+    pub fn test_field_rowcol_roundtrip() {
+        for field in ALL_INDEXES {
+            let (row, col) = field2rowcol(field);
+            assert_eq!(rowcol2field(row, col), field);
+        }
+    }
+
+    #[test]
+    pub fn test_step_field() {
+        assert_eq!(step_field(4, (-1, 0)), Some(1));
+        assert_eq!(step_field(4, (1, 1)), Some(8));
+        assert_eq!(step_field(0, (-1, 0)), None);
+        assert_eq!(step_field(0, (0, -1)), None);
+        assert_eq!(step_field(11, (1, 0)), None);
+        assert_eq!(step_field(11, (0, 1)), None);
+        assert_eq!(step_field(2, (1, -1)), Some(4));
+    }
+
+    #[test]
+    pub fn test_hand_equality_ignores_zero_counts() {
+        let empty = Hand::empty();
+        let zero = Hand::from([(HandPiece::Bishop, 0)]);
+        let one = Hand::from([(HandPiece::Bishop, 1)]);
+
+        assert_eq!(zero, empty);
+        assert_ne!(one, empty);
+        assert_ne!(one, zero);
+    }
+
+    #[test]
+    pub fn test_hand_add_remove() {
+        let mut hand = Hand::empty();
+
+        hand.add(HandPiece::Rook);
+        hand.add(HandPiece::Rook);
+        hand.add(HandPiece::Pawn);
+        assert_eq!(hand.count(HandPiece::Rook), 2);
+        assert_eq!(
+            hand.iter().collect::<Vec<_>>(),
+            vec![(HandPiece::Pawn, 1), (HandPiece::Rook, 2)]
+        );
+
+        assert!(hand.remove(HandPiece::Pawn));
+        assert!(!hand.remove(HandPiece::Pawn));
+        assert!(!hand.remove(HandPiece::Bishop));
+        assert_eq!(hand, Hand::from([(HandPiece::Rook, 2)]));
+        assert!(!hand.is_empty());
+    }
+
+    #[test]
+    pub fn test_setters_keep_caches() {
+        let mut position = Position::startpos();
+        position.set_piece(4, None);
+        position.set_piece(3, Some(BLACK_PROMOTED_PAWN));
+        position.set_hand(Color::White, Hand::from([(HandPiece::Pawn, 1)]));
+        position.set_to_play(Color::White);
+
+        let mut board = Position::startpos().board;
+        board[4] = None;
+        board[3] = Some(BLACK_PROMOTED_PAWN);
+        let expected = Position::new(
+            board,
+            Color::White,
+            Hand::empty(),
+            Hand::from([(HandPiece::Pawn, 1)]),
+        );
+        assert_eq!(position, expected);
+        assert_eq!(position.key(), expected.key());
+        assert_eq!(position.bitboard(), expected.bitboard());
+        assert_eq!(position.legal_moves(), expected.legal_moves());
+    }
+
+    #[test]
+    pub fn test_hand_holds_at_most_two_pieces_of_a_kind() {
+        let mut hand = Hand::from([(HandPiece::Pawn, 3)]);
+        assert_eq!(hand.count(HandPiece::Pawn), MAX_HAND_COUNT);
+
+        assert!(!hand.add(HandPiece::Pawn));
+        assert_eq!(hand.count(HandPiece::Pawn), MAX_HAND_COUNT);
+    }
 }
@@ -0,0 +1,665 @@
+use std::sync::OnceLock;
+
+use crate::game::*;
+
+/// Whether left-right mirror images share an index.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub enum IndexMode {
+    /// Every position has its own index.
+    #[default]
+    Full,
+    /// A position and its mirror image share one index, which nearly halves
+    /// the index space. Lion placements which are their own mirror image,
+    /// with the lions on the middle column or captured, keep the range of
+    /// both images of the other pieces, so that the indexes of the
+    /// non-canonical images stand for no position.
+    Mirror,
+}
+
+/// Number of pieces of every hand piece kind, on the board and in the
+/// hands together, in the order of [`ALL_HAND_PIECES`].
+pub(crate) type Material = [usize; 3];
+
+/// Material a game is played with.
+pub(crate) const GAME_MATERIAL: Material = [MAX_HAND_COUNT; 3];
+
+/// Board variants of the pieces of every hand piece kind, in the order of
+/// [`ALL_HAND_PIECES`].
+const VARIANTS: [&[Piece]; 3] = [
+    &[
+        BLACK_PAWN,
+        BLACK_PROMOTED_PAWN,
+        WHITE_PAWN,
+        WHITE_PROMOTED_PAWN,
+    ],
+    &[BLACK_BISHOP, WHITE_BISHOP],
+    &[BLACK_ROOK, WHITE_ROOK],
+];
+
+/// Fields of the black and the white lion, `None` once a lion is captured.
+type Lions = (Option<Field>, Option<Field>);
+
+fn binomial(n: usize, k: usize) -> u64 {
+    match k {
+        0 => 1,
+        1 => n as u64,
+        _ if n < 2 => 0,
+        _ => (n * (n - 1) / 2) as u64,
+    }
+}
+
+fn mirror_lions((black, white): Lions) -> Lions {
+    (black.map(mirror_field), white.map(mirror_field))
+}
+
+fn lions_slot(field: Option<Field>) -> usize {
+    field.unwrap_or(BOARD_SIZE)
+}
+
+/// Fields not taken by a lion, shrinking as the pieces of every kind are
+/// placed.
+#[derive(Copy, Clone)]
+struct FreeFields {
+    fields: [Field; BOARD_SIZE],
+    len: usize,
+}
+
+impl FreeFields {
+    fn new(lions: Lions) -> Self {
+        let mut free = FreeFields {
+            fields: [0; BOARD_SIZE],
+            len: 0,
+        };
+        for field in ALL_INDEXES {
+            if Some(field) != lions.0 && Some(field) != lions.1 {
+                free.fields[free.len] = field;
+                free.len += 1;
+            }
+        }
+        free
+    }
+
+    fn fields(&self) -> &[Field] {
+        &self.fields[..self.len]
+    }
+
+    fn len(&self) -> usize {
+        self.len
+    }
+
+    fn remove(&mut self, offset: usize) {
+        self.fields.copy_within(offset + 1..self.len, offset);
+        self.len -= 1;
+    }
+}
+
+/// Dense ranking of the positions with a given material.
+///
+/// Positions are ordered by the placement of the lions, with placements
+/// of both lions first, then by the placement of the other pieces on the
+/// free fields and in the hands, kind by kind, and last by the side to play.
+pub(crate) struct Indexer {
+    mode: IndexMode,
+    /// Splits of every kind between the board, the black hand and the
+    /// white hand.
+    configs: [Vec<(usize, usize, usize)>; 3],
+    /// Ways to place the kinds from the given one onwards on a number of
+    /// free fields.
+    counts: [[u64; 4]; BOARD_SIZE + 1],
+    /// Lion placements in index order, with the first rank of their range.
+    placements: Vec<(Lions, u64)>,
+    /// Placement of every pair of lion fields, with whether the board has to
+    /// be mirrored to reach it.
+    lookup: [[(usize, bool); BOARD_SIZE + 1]; BOARD_SIZE + 1],
+    size: u64,
+    lions_size: u64,
+}
+
+impl Indexer {
+    pub(crate) fn new(material: Material, mode: IndexMode) -> Self {
+        let configs = material.map(|count| {
+            let mut configs = vec![];
+            for on_board in 0..=count {
+                for black in 0..=count - on_board {
+                    configs.push((on_board, black, count - on_board - black));
+                }
+            }
+            configs
+        });
+
+        let mut indexer = Indexer {
+            mode,
+            configs,
+            counts: [[1; 4]; BOARD_SIZE + 1],
+            placements: vec![],
+            lookup: [[(0, false); BOARD_SIZE + 1]; BOARD_SIZE + 1],
+            size: 0,
+            lions_size: 0,
+        };
+        for kind in (0..VARIANTS.len()).rev() {
+            for free in 0..=BOARD_SIZE {
+                indexer.counts[free][kind] = (0..indexer.configs[kind].len())
+                    .map(|config| indexer.block_size(free, kind, config))
+                    .sum();
+            }
+        }
+
+        let mut all: Vec<Lions> = vec![];
+        for black in ALL_INDEXES {
+            for white in ALL_INDEXES.into_iter().filter(|&white| white != black) {
+                all.push((Some(black), Some(white)));
+            }
+        }
+        all.extend(ALL_INDEXES.map(|black| (Some(black), None)));
+        all.extend(ALL_INDEXES.map(|white| (None, Some(white))));
+        all.push((None, None));
+        let rank = |lions| all.iter().position(|&other| other == lions).unwrap();
+
+        let mut first = 0;
+        for &lions in &all {
+            if (lions.0.is_none() || lions.1.is_none()) && indexer.lions_size == 0 {
+                indexer.lions_size = 2 * first;
+            }
+            if mode == IndexMode::Mirror && rank(mirror_lions(lions)) < rank(lions) {
+                continue;
+            }
+            indexer.placements.push((lions, first));
+            let lions_count = lions.0.is_some() as usize + lions.1.is_some() as usize;
+            first += indexer.counts[BOARD_SIZE - lions_count][0];
+        }
+        indexer.size = 2 * first;
+
+        for &lions in &all {
+            let placement = |lions| {
+                indexer
+                    .placements
+                    .iter()
+                    .position(|&(other, _)| other == lions)
+            };
+            let slot = match placement(lions) {
+                Some(placement) => (placement, false),
+                None => (placement(mirror_lions(lions)).unwrap(), true),
+            };
+            indexer.lookup[lions_slot(lions.0)][lions_slot(lions.1)] = slot;
+        }
+        indexer
+    }
+
+    /// Number of positions, or of ranks including the unused ones in
+    /// `Mirror` mode.
+    pub(crate) fn size(&self) -> u64 {
+        self.size
+    }
+
+    /// Ranks below this one stand for the positions with both lions on
+    /// the board.
+    pub(crate) fn lions_size(&self) -> u64 {
+        self.lions_size
+    }
+
+    fn block_size(&self, free: usize, kind: usize, config: usize) -> u64 {
+        let (on_board, _, _) = self.configs[kind][config];
+        if on_board > free {
+            return 0;
+        }
+        let variants = VARIANTS[kind].len() as u64;
+        binomial(free, on_board)
+            * variants.pow(on_board as u32)
+            * self.counts[free - on_board][kind + 1]
+    }
+
+    /// Rank of the pieces other than the lions on the free fields and in
+    /// the hands.
+    fn rank_pieces(&self, board: &Board, hands: [&Hand; 2], mut free: FreeFields) -> u64 {
+        let mut rank = 0;
+        for (kind, variants) in VARIANTS.into_iter().enumerate() {
+            let hand_piece = ALL_HAND_PIECES[kind];
+            let radix = variants.len() as u64;
+
+            let mut local = 0;
+            let mut taken = [0; MAX_HAND_COUNT];
+            let mut on_board = 0;
+            for (offset, &field) in free.fields().iter().enumerate() {
+                if let Some(variant) = variants
+                    .iter()
+                    .position(|&piece| board[field] == Some(piece))
+                {
+                    assert!(on_board < MAX_HAND_COUNT, "more than two pieces of a kind");
+                    local = local * radix + variant as u64;
+                    taken[on_board] = offset;
+                    on_board += 1;
+                }
+            }
+            let fields_rank = match on_board {
+                0 => 0,
+                1 => taken[0] as u64,
+                _ => binomial(taken[1], 2) + taken[0] as u64,
+            };
+            local += fields_rank * radix.pow(on_board as u32);
+
+            let config = (
+                on_board,
+                hands[0].count(hand_piece),
+                hands[1].count(hand_piece),
+            );
+            let config = self.configs[kind]
+                .iter()
+                .position(|&other| other == config)
+                .expect("a position with the material of the index");
+            let offset: u64 = (0..config)
+                .map(|config| self.block_size(free.len(), kind, config))
+                .sum();
+
+            for &offset in taken[..on_board].iter().rev() {
+                free.remove(offset);
+            }
+            rank += offset + local * self.counts[free.len()][kind + 1];
+        }
+        rank
+    }
+
+    fn unrank_pieces(
+        &self,
+        mut rank: u64,
+        board: &mut Board,
+        hands: &mut [Hand; 2],
+        mut free: FreeFields,
+    ) {
+        for (kind, variants) in VARIANTS.into_iter().enumerate() {
+            let hand_piece = ALL_HAND_PIECES[kind];
+            let radix = variants.len() as u64;
+
+            let config = (0..self.configs[kind].len())
+                .find(|&config| {
+                    let size = self.block_size(free.len(), kind, config);
+                    if rank < size {
+                        return true;
+                    }
+                    rank -= size;
+                    false
+                })
+                .expect("rank in range");
+            let (on_board, black_count, white_count) = self.configs[kind][config];
+
+            let sub_count = self.counts[free.len() - on_board][kind + 1];
+            let (mut local, rest) = (rank / sub_count, rank % sub_count);
+            let mut variant_digits = [0; MAX_HAND_COUNT];
+            for digit in variant_digits[..on_board].iter_mut().rev() {
+                *digit = (local % radix) as usize;
+                local /= radix;
+            }
+            let taken = match on_board {
+                0 => [0, 0],
+                1 => [local as usize, 0],
+                _ => {
+                    let second = (1..free.len())
+                        .rev()
+                        .find(|&second| binomial(second, 2) <= local)
+                        .unwrap();
+                    [(local - binomial(second, 2)) as usize, second]
+                }
+            };
+
+            for (&offset, &variant) in taken[..on_board].iter().zip(&variant_digits) {
+                board[free.fields()[offset]] = Some(variants[variant]);
+            }
+            hands[0].set(hand_piece, black_count);
+            hands[1].set(hand_piece, white_count);
+
+            for &offset in taken[..on_board].iter().rev() {
+                free.remove(offset);
+            }
+            rank = rest;
+        }
+    }
+
+    /// Rank of the position, ignoring the move number.
+    pub(crate) fn index(&self, position: &Position) -> u64 {
+        let lions = (
+            position.king_field(Color::Black),
+            position.king_field(Color::White),
+        );
+        let (placement, mirror) = self.lookup[lions_slot(lions.0)][lions_slot(lions.1)];
+        let (lions, first) = self.placements[placement];
+
+        let board = if mirror {
+            position.board.mirrored()
+        } else {
+            position.board
+        };
+        let hands = [&position.black_hand, &position.white_hand];
+        let free = FreeFields::new(lions);
+        let mut rank = self.rank_pieces(&board, hands, free);
+        if self.mode == IndexMode::Mirror && mirror_lions(lions) == lions {
+            rank = rank.min(self.rank_pieces(&board.mirrored(), hands, free));
+        }
+
+        (first + rank) * 2 + position.to_play as u64
+    }
+
+    /// Position with the given rank, or `None` if the rank is out of range
+    /// or unused.
+    pub(crate) fn position(&self, index: u64) -> Option<Position> {
+        if index >= self.size {
+            return None;
+        }
+        let to_play = if index.is_multiple_of(2) {
+            Color::Black
+        } else {
+            Color::White
+        };
+        let rank = index / 2;
+        let placement = self.placements.partition_point(|&(_, first)| first <= rank) - 1;
+        let (lions, first) = self.placements[placement];
+
+        let mut board = Board::empty();
+        if let Some(black) = lions.0 {
+            board[black] = Some(BLACK_KING);
+        }
+        if let Some(white) = lions.1 {
+            board[white] = Some(WHITE_KING);
+        }
+        let mut hands = [Hand::empty(), Hand::empty()];
+        let free = FreeFields::new(lions);
+        self.unrank_pieces(rank - first, &mut board, &mut hands, free);
+
+        if self.mode == IndexMode::Mirror
+            && mirror_lions(lions) == lions
+            && self.rank_pieces(&board.mirrored(), [&hands[0], &hands[1]], free) < rank - first
+        {
+            return None;
+        }
+        Some(Position::new(board, to_play, hands[0], hands[1]))
+    }
+}
+
+fn indexer(mode: IndexMode) -> &'static Indexer {
+    static FULL: OnceLock<Indexer> = OnceLock::new();
+    static MIRROR: OnceLock<Indexer> = OnceLock::new();
+    match mode {
+        IndexMode::Full => FULL.get_or_init(|| Indexer::new(GAME_MATERIAL, mode)),
+        IndexMode::Mirror => MIRROR.get_or_init(|| Indexer::new(GAME_MATERIAL, mode)),
+    }
+}
+
+impl IndexMode {
+    /// Number of indexes of the positions of a game. All of them stand for
+    /// a position in `Full` mode.
+    pub fn size(self) -> u64 {
+        indexer(self).size()
+    }
+}
+
+impl Position {
+    /// Pieces of every hand piece kind on the board and in the hands.
+    pub(crate) fn material(&self) -> Material {
+        let mut material = ALL_HAND_PIECES
+            .map(|piece| self.black_hand.count(piece) + self.white_hand.count(piece));
+        for piece in self.board.0.into_iter().flatten() {
+            if let Ok(hand_piece) = HandPiece::try_from(piece.kind) {
+                material[hand_piece as usize] += 1;
+            }
+        }
+        material
+    }
+
+    /// Index of the position, ignoring the move number.
+    ///
+    /// The indexes of all positions with the material of a game, which every
+    /// position accepted by the SFEN parser or reached by moves has, are
+    /// exactly the numbers below `IndexMode::Full.size()`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the position has other material.
+    pub fn index(&self) -> u64 {
+        self.index_with(IndexMode::Full)
+    }
+
+    pub fn index_with(&self, mode: IndexMode) -> u64 {
+        indexer(mode).index(self)
+    }
+
+    /// Position with the given index, or `None` if the index is out of range.
+    pub fn from_index(index: u64) -> Option<Self> {
+        Position::from_index_with(index, IndexMode::Full)
+    }
+
+    /// Position with the given index, or `None` if the index is out of
+    /// range or, in `Mirror` mode, stands for no position.
+    pub fn from_index_with(index: u64, mode: IndexMode) -> Option<Self> {
+        indexer(mode).position(index)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use super::*;
+    use crate::parser::parse_sfen;
+    use crate::sfen::Sfen;
+
+    const SFENS: [&str; 6] = [
+        "rkb/1p1/1P1/BKR b -",
+        "1k1/1p+1/1P+1/1K1 w B2R2",
+        "3/1k1/3/1K1 b P2B2r2",
+        "r1b/1k1/3/BPK w Pr",
+        "k2/1P1/3/2K b BRbpr",
+        "2k/1p+1/R2/K1b w PBr",
+    ];
+
+    fn sample_indexes(size: u64) -> Vec<u64> {
+        let mut indexes: Vec<u64> = (0..2000).chain(size - 2000..size).collect();
+        let mut state = 0x2545f4914f6cdd1du64;
+        for _ in 0..5000 {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            indexes.push(state % size);
+        }
+        indexes
+    }
+
+    #[test]
+    fn test_sizes() {
+        assert_eq!(IndexMode::Full.size(), 4_317_868_158);
+        assert_eq!(IndexMode::Mirror.size(), 2_526_128_742);
+        assert_eq!(
+            Indexer::new(GAME_MATERIAL, IndexMode::Full).lions_size(),
+            2 * 132 * 11_878_227
+        );
+    }
+
+    #[test]
+    fn test_index_is_a_bijection() {
+        // Positions with a chick and a giraffe, counted piece by piece.
+        let mut positions = HashSet::new();
+        let fields = || ALL_INDEXES.into_iter().map(Some).chain([None]);
+        for black_lion in fields() {
+            for white_lion in fields().filter(|&f| f.is_none() || f != black_lion) {
+                for chick in fields() {
+                    for giraffe in fields() {
+                        let taken = [black_lion, white_lion, chick, giraffe];
+                        let on_board: Vec<Field> = taken.into_iter().flatten().collect();
+                        if (1..on_board.len()).any(|i| on_board[i..].contains(&on_board[i - 1])) {
+                            continue;
+                        }
+                        let chicks: &[(Option<Piece>, Color)] = match chick {
+                            Some(_) => &[
+                                (Some(BLACK_PAWN), Color::Black),
+                                (Some(BLACK_PROMOTED_PAWN), Color::Black),
+                                (Some(WHITE_PAWN), Color::Black),
+                                (Some(WHITE_PROMOTED_PAWN), Color::Black),
+                            ],
+                            None => &[(None, Color::Black), (None, Color::White)],
+                        };
+                        let giraffes: &[(Option<Piece>, Color)] = match giraffe {
+                            Some(_) => &[
+                                (Some(BLACK_ROOK), Color::Black),
+                                (Some(WHITE_ROOK), Color::Black),
+                            ],
+                            None => &[(None, Color::Black), (None, Color::White)],
+                        };
+                        for &(chick_piece, chick_hand) in chicks {
+                            for &(giraffe_piece, giraffe_hand) in giraffes {
+                                let mut board = Board::empty();
+                                let mut hands = [Hand::empty(), Hand::empty()];
+                                for (field, piece) in [
+                                    (black_lion, Some(BLACK_KING)),
+                                    (white_lion, Some(WHITE_KING)),
+                                    (chick, chick_piece),
+                                    (giraffe, giraffe_piece),
+                                ] {
+                                    if let Some(field) = field {
+                                        board[field] = piece;
+                                    }
+                                }
+                                if chick.is_none() {
+                                    hands[chick_hand as usize].add(HandPiece::Pawn);
+                                }
+                                if giraffe.is_none() {
+                                    hands[giraffe_hand as usize].add(HandPiece::Rook);
+                                }
+                                for to_play in [Color::Black, Color::White] {
+                                    positions.insert(
+                                        Position::new(board, to_play, hands[0], hands[1]).sfen(),
+                                    );
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+        }
+
+        let indexer = Indexer::new([1, 0, 1], IndexMode::Full);
+        assert_eq!(indexer.size(), positions.len() as u64);
+        for index in 0..indexer.size() {
+            let position = indexer.position(index).unwrap();
+            assert!(positions.contains(&position.sfen()), "{}", position);
+            assert_eq!(indexer.index(&position), index, "{}", position);
+        }
+    }
+
+    #[test]
+    fn test_position_roundtrip() {
+        for sfen in SFENS {
+            let position = parse_sfen(sfen).unwrap();
+            let index = position.index();
+
+            assert!(index < IndexMode::Full.size());
+            assert_eq!(Position::from_index(index), Some(position), "{}", sfen);
+        }
+    }
+
+    #[test]
+    fn test_move_number_is_ignored() {
+        let position = parse_sfen("rkb/1p1/1P1/BKR b - 7").unwrap();
+
+        assert_eq!(position.index(), Position::startpos().index());
+    }
+
+    #[test]
+    fn test_index_roundtrip() {
+        for mode in [IndexMode::Full, IndexMode::Mirror] {
+            for index in sample_indexes(mode.size()) {
+                if let Some(position) = Position::from_index_with(index, mode) {
+                    assert_eq!(position.index_with(mode), index, "{}", position);
+                } else {
+                    assert_eq!(mode, IndexMode::Mirror);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_indexed_positions_parse() {
+        for mode in [IndexMode::Full, IndexMode::Mirror] {
+            for index in sample_indexes(mode.size()) {
+                if let Some(position) = Position::from_index_with(index, mode) {
+                    assert_eq!(parse_sfen(&position.sfen()), Ok(position), "{}", position);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_out_of_range() {
+        assert_eq!(Position::from_index(IndexMode::Full.size()), None);
+        assert_eq!(
+            Position::from_index_with(IndexMode::Mirror.size(), IndexMode::Mirror),
+            None
+        );
+    }
+
+    #[test]
+    fn test_positions_without_lions() {
+        let mut position = Position::startpos();
+        position.make_move(Move::Board { from: 7, to: 4 });
+        position.make_move(Move::Board { from: 0, to: 3 });
+        position.make_move(Move::Board { from: 4, to: 1 });
+
+        for position in [position, parse_sfen("3/1k1/3/3 b P2B2R2").unwrap()] {
+            assert_eq!(Position::from_index(position.index()), Some(position));
+        }
+    }
+
+    #[test]
+    fn test_mirror_images_share_index() {
+        for sfen in SFENS {
+            let position = parse_sfen(sfen).unwrap();
+            let mirrored = position.mirrored();
+
+            assert_eq!(
+                position.index_with(IndexMode::Mirror),
+                mirrored.index_with(IndexMode::Mirror),
+                "{}",
+                sfen
+            );
+        }
+    }
+
+    #[test]
+    fn test_mirror_index_with_lions_on_middle_column() {
+        let position = Position::startpos();
+        let mirrored = position.mirrored();
+
+        assert_eq!(
+            position.index_with(IndexMode::Mirror),
+            mirrored.index_with(IndexMode::Mirror)
+        );
+    }
+
+    #[test]
+    fn test_mirror_index_keeps_canonical_positions() {
+        for sfen in SFENS {
+            let position = parse_sfen(sfen).unwrap();
+            let index = position.index_with(IndexMode::Mirror);
+
+            let restored = Position::from_index_with(index, IndexMode::Mirror).unwrap();
+            assert!(
+                restored == position || restored == position.mirrored(),
+                "{}",
+                sfen
+            );
+        }
+    }
+
+    #[test]
+    fn test_mirror_index_covers_every_position() {
+        let full = Indexer::new([1, 0, 0], IndexMode::Full);
+        let mirror = Indexer::new([1, 0, 0], IndexMode::Mirror);
+
+        let mut used = HashSet::new();
+        for index in 0..full.size() {
+            let position = full.position(index).unwrap();
+            let mirror_index = mirror.index(&position);
+            assert_eq!(mirror_index, mirror.index(&position.mirrored()));
+            used.insert(mirror_index);
+        }
+        for index in 0..mirror.size() {
+            assert_eq!(mirror.position(index).is_some(), used.contains(&index));
+        }
+    }
+}
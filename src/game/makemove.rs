@@ -0,0 +1,246 @@
+use crate::game::*;
+
+/// Information needed to take a move back.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Undo {
+    pub captured: Option<Piece>,
+    pub promoted: bool,
+}
+
+/// Row on which chicks of the given color promote.
+pub fn promotion_row(color: Color) -> Row {
+    match color {
+        Color::Black => 0,
+        Color::White => ROWS - 1,
+    }
+}
+
+impl Position {
+    /// Plays the move for the side to play.
+    ///
+    /// The move is assumed to be legal. Captured pieces go to the hand
+    /// of the mover (a hen goes back as a chick) and a chick reaching
+    /// the last row is promoted.
+    pub fn make_move(&mut self, mv: Move) -> Undo {
+        let color = self.to_play;
+        let undo = match mv {
+            Move::Board { from, to } => {
+                let mut piece = self.take_piece(from).expect("no piece to move");
+                let captured = self.take_piece(to);
+
+                if let Some(captured) = captured {
+                    if let Ok(hand_piece) = HandPiece::try_from(captured.kind) {
+                        self.add_to_hand(color, hand_piece);
+                    }
+                }
+
+                let (row, _) = field2rowcol(to);
+                let promoted = piece.kind == PieceKind::Pawn && row == promotion_row(color);
+                if promoted {
+                    piece.kind = PieceKind::PromotedPawn;
+                }
+
+                self.put_piece(to, piece);
+                Undo { captured, promoted }
+            }
+            Move::Drop { piece, to } => {
+                let removed = self.remove_from_hand(color, piece);
+                debug_assert!(removed, "dropping a piece not in hand");
+                self.put_piece(
+                    to,
+                    Piece {
+                        kind: piece.into(),
+                        color,
+                    },
+                );
+                Undo {
+                    captured: None,
+                    promoted: false,
+                }
+            }
+        };
+
+        self.set_to_play(color.opposite());
+        if let Some(move_number) = self.move_number.as_mut() {
+            *move_number += 1;
+        }
+        undo
+    }
+
+    /// Takes back a move made with [`Position::make_move`],
+    /// restoring the exact previous position.
+    pub fn unmake_move(&mut self, mv: Move, undo: Undo) {
+        let color = self.to_play.opposite();
+        self.set_to_play(color);
+        if let Some(move_number) = self.move_number.as_mut() {
+            *move_number -= 1;
+        }
+
+        match mv {
+            Move::Board { from, to } => {
+                let mut piece = self.take_piece(to).expect("no piece to take back");
+                if undo.promoted {
+                    piece.kind = PieceKind::Pawn;
+                }
+                self.put_piece(from, piece);
+
+                if let Some(captured) = undo.captured {
+                    if let Ok(hand_piece) = HandPiece::try_from(captured.kind) {
+                        self.remove_from_hand(color, hand_piece);
+                    }
+                    self.put_piece(to, captured);
+                }
+            }
+            Move::Drop { piece, to } => {
+                self.take_piece(to);
+                self.add_to_hand(color, piece);
+            }
+        }
+    }
+
+    pub(super) fn take_piece(&mut self, field: Field) -> Option<Piece> {
+        let piece = self.board[field].take();
+        self.bitboard.take(field);
+        if let Some(piece) = piece {
+            self.key ^= piece_key(field, piece);
+        }
+        piece
+    }
+
+    pub(super) fn put_piece(&mut self, field: Field, piece: Piece) {
+        self.board[field] = Some(piece);
+        self.bitboard.put(field, piece);
+        self.key ^= piece_key(field, piece);
+    }
+
+    pub(super) fn add_to_hand(&mut self, color: Color, piece: HandPiece) -> bool {
+        let count = self.hand(color).count(piece);
+        let added = self.hand_mut(color).add(piece);
+        if added {
+            self.key ^= hand_key(color, piece, count) ^ hand_key(color, piece, count + 1);
+        }
+        added
+    }
+
+    pub(super) fn remove_from_hand(&mut self, color: Color, piece: HandPiece) -> bool {
+        let count = self.hand(color).count(piece);
+        let removed = self.hand_mut(color).remove(piece);
+        if removed {
+            self.key ^= hand_key(color, piece, count) ^ hand_key(color, piece, count - 1);
+        }
+        removed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse_sfen;
+    use crate::sfen::Sfen;
+
+    #[test]
+    fn test_simple_move() {
+        let mut position = parse_sfen("rkb/1p1/1P1/BKR b -").unwrap();
+
+        position.make_move(Move::Board { from: 10, to: 6 });
+
+        assert_eq!(position, parse_sfen("rkb/1p1/KP1/B1R w -").unwrap());
+    }
+
+    #[test]
+    fn test_capture_goes_to_hand() {
+        let mut position = parse_sfen("rkb/1p1/1P1/BKR b -").unwrap();
+
+        let undo = position.make_move(Move::Board { from: 7, to: 4 });
+
+        assert_eq!(undo.captured, Some(WHITE_PAWN));
+        assert_eq!(position.sfen(), "rkb/1P1/3/BKR w P");
+    }
+
+    #[test]
+    fn test_captured_hen_is_demoted() {
+        let mut position = parse_sfen("1k1/3/1p+1/1R1 b B2pr").unwrap();
+
+        position.make_move(Move::Board { from: 10, to: 7 });
+
+        assert_eq!(position, parse_sfen("1k1/3/1R1/3 w PB2pr").unwrap());
+    }
+
+    #[test]
+    fn test_promotion() {
+        let mut position = parse_sfen("k2/1P1/3/2K b PB2R2").unwrap();
+
+        let undo = position.make_move(Move::Board { from: 4, to: 1 });
+
+        assert!(undo.promoted);
+        assert_eq!(position.sfen(), "kP+1/3/3/2K w PB2R2");
+    }
+
+    #[test]
+    fn test_white_promotion() {
+        let mut position = parse_sfen("k2/3/1p1/2K w pb2r2").unwrap();
+
+        position.make_move(Move::Board { from: 7, to: 10 });
+
+        assert_eq!(position.sfen(), "k2/3/3/1p+K b pb2r2");
+    }
+
+    #[test]
+    fn test_drop_does_not_promote() {
+        let mut position = parse_sfen("k2/3/3/2K b PB2R2p").unwrap();
+
+        let undo = position.make_move(Move::Drop {
+            piece: HandPiece::Pawn,
+            to: 1,
+        });
+
+        assert!(!undo.promoted);
+        assert_eq!(position, parse_sfen("kP1/3/3/2K w B2R2p").unwrap());
+    }
+
+    #[test]
+    fn test_lion_capture_does_not_go_to_hand() {
+        let mut position = parse_sfen("1k1/1R1/3/1K1 b P2B2r").unwrap();
+
+        position.make_move(Move::Board { from: 4, to: 1 });
+
+        assert_eq!(position, parse_sfen("1R1/3/3/1K1 w P2B2r").unwrap());
+    }
+
+    #[test]
+    fn test_unmake_restores_position() {
+        let sfens = [
+            "rkb/1p1/1P1/BKR b -",
+            "rkb/1p1/1P1/BKR w -",
+            "1k1/3/1p+1/1R1 b B2pr",
+            "k2/1P1/3/2K b PB2R2",
+            "k2/3/1p1/2K w pb2r2",
+            "1k1/1R1/3/1K1 b P2B2r",
+            "r1b/1k1/1P1/B1K b Pr",
+            "1k1/p2/3/1K1 w PBRbr",
+        ];
+
+        for sfen in sfens {
+            let mut position = parse_sfen(sfen).unwrap();
+            let original = position;
+
+            for mv in original.legal_moves() {
+                let undo = position.make_move(mv);
+                position.unmake_move(mv, undo);
+                assert_eq!(position, original, "{} {:?}", sfen, mv);
+            }
+        }
+    }
+
+    #[test]
+    fn test_move_number() {
+        let mut position = parse_sfen("rkb/1p1/1P1/BKR b - 1").unwrap();
+        let mv = Move::Board { from: 7, to: 4 };
+
+        let undo = position.make_move(mv);
+        assert_eq!(position.move_number, Some(2));
+
+        position.unmake_move(mv, undo);
+        assert_eq!(position.move_number, Some(1));
+    }
+}
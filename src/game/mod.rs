@@ -1,5 +1,20 @@
+mod bitboard;
 mod board;
+mod index;
+mod makemove;
+mod movegen;
+mod moves;
 mod piece;
+mod result;
+mod symmetry;
+mod zobrist;
 
+pub use bitboard::*;
 pub use board::*;
+pub use index::*;
+pub use makemove::*;
+pub use moves::*;
 pub use piece::*;
+pub use result::*;
+pub(crate) use symmetry::*;
+pub use zobrist::*;
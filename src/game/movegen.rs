@@ -0,0 +1,186 @@
+use crate::game::*;
+
+impl Position {
+    /// All moves available to the side to play.
+    ///
+    /// In Dobutsu Shogi there is no check rule: moving the lion into an
+    /// attacked field is allowed (and loses), so every move a piece can
+    /// physically make is legal.
+    pub fn legal_moves(&self) -> Vec<Move> {
+        let mut moves = vec![];
+        self.generate_board_moves(&mut moves);
+        self.generate_drops(&mut moves);
+        moves
+    }
+
+    fn generate_board_moves(&self, moves: &mut Vec<Move>) {
+        let own = self.bitboard.color(self.to_play);
+        for from in fields(own) {
+            let piece = self.board[from].expect("a piece of the side to play");
+            for to in fields(attacks(from, piece) & !own) {
+                moves.push(Move::Board { from, to });
+            }
+        }
+    }
+
+    fn generate_drops(&self, moves: &mut Vec<Move>) {
+        let hand = self.hand(self.to_play);
+        let empty = !self.bitboard.occupied() & FULL_BOARD;
+        for (piece, _) in hand.iter() {
+            for to in fields(empty) {
+                moves.push(Move::Drop { piece, to });
+            }
+        }
+    }
+
+    /// Whether any piece of color `by` can move to `field`.
+    pub fn is_attacked(&self, field: Field, by: Color) -> bool {
+        self.bitboard.attackers(field, by) != 0
+    }
+
+    /// Field occupied by the lion of the given color.
+    pub fn king_field(&self, color: Color) -> Option<Field> {
+        let king = Piece {
+            kind: PieceKind::King,
+            color,
+        };
+        fields(self.bitboard.pieces(king)).next()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse_sfen;
+
+    fn board_move(from: Field, to: Field) -> Move {
+        Move::Board { from, to }
+    }
+
+    fn sorted(mut moves: Vec<Move>) -> Vec<Move> {
+        moves.sort_by_key(|m| format!("{:?}", m));
+        moves
+    }
+
+    #[test]
+    fn test_starting_position_moves() {
+        let position = parse_sfen("rkb/1p1/1P1/BKR b -").unwrap();
+
+        let result = sorted(position.legal_moves());
+
+        let expected = sorted(vec![
+            board_move(7, 4),
+            board_move(10, 6),
+            board_move(10, 8),
+            board_move(11, 8),
+        ]);
+        assert_eq!(result, expected);
+    }
+
+    #[test]
+    fn test_starting_position_moves_white() {
+        let position = parse_sfen("rkb/1p1/1P1/BKR w -").unwrap();
+
+        let result = sorted(position.legal_moves());
+
+        let expected = sorted(vec![
+            board_move(4, 7),
+            board_move(1, 3),
+            board_move(1, 5),
+            board_move(0, 3),
+        ]);
+        assert_eq!(result, expected);
+    }
+
+    #[test]
+    fn test_piece_step_patterns() {
+        let inputs = [
+            ("3/1P1/3/3 b pb2r2", vec![1]),
+            ("3/1B1/3/3 b p2br2", vec![0, 2, 6, 8]),
+            ("3/1R1/3/3 b p2b2r", vec![1, 3, 5, 7]),
+            ("3/1K1/3/3 b p2b2r2", vec![0, 1, 2, 3, 5, 6, 7, 8]),
+            ("3/1P+1/3/3 b pb2r2", vec![0, 1, 2, 3, 5, 7]),
+            ("3/1p1/3/3 w PB2R2", vec![7]),
+            ("3/1p+1/3/3 w PB2R2", vec![1, 3, 5, 6, 7, 8]),
+        ];
+
+        for (sfen, expected) in inputs {
+            let position = parse_sfen(sfen).unwrap();
+            let mut result: Vec<Field> = position.legal_moves().iter().map(Move::to).collect();
+            result.sort();
+            assert_eq!(result, expected, "{}", sfen);
+        }
+    }
+
+    #[test]
+    fn test_pieces_on_edges() {
+        let position = parse_sfen("K2/3/3/3 b p2b2r2").unwrap();
+
+        let mut result: Vec<Field> = position.legal_moves().iter().map(Move::to).collect();
+        result.sort();
+
+        assert_eq!(result, vec![1, 3, 4]);
+    }
+
+    #[test]
+    fn test_captures_but_no_own_pieces() {
+        let position = parse_sfen("3/pRP/3/3 b b2r").unwrap();
+
+        let mut result: Vec<Field> = position.legal_moves().iter().map(Move::to).collect();
+        result.sort();
+
+        assert_eq!(result, vec![1, 2, 3, 7]);
+    }
+
+    #[test]
+    fn test_drops() {
+        let position = parse_sfen("k2/3/3/2K b Ppb2r2").unwrap();
+        assert_eq!(position.black_hand.count(HandPiece::Pawn), 1);
+
+        let drops: Vec<Move> = position
+            .legal_moves()
+            .into_iter()
+            .filter(|m| matches!(m, Move::Drop { .. }))
+            .collect();
+
+        assert_eq!(drops.len(), 10);
+        assert!(drops.iter().all(|m| matches!(
+            m,
+            Move::Drop {
+                piece: HandPiece::Pawn,
+                ..
+            }
+        )));
+    }
+
+    #[test]
+    fn test_drops_of_every_hand_piece() {
+        let position = parse_sfen("k2/3/3/2K w PR2pb2").unwrap();
+
+        let drops = position
+            .legal_moves()
+            .into_iter()
+            .filter(|m| matches!(m, Move::Drop { .. }))
+            .count();
+
+        assert_eq!(drops, 2 * 10);
+    }
+
+    #[test]
+    fn test_is_attacked() {
+        let position = parse_sfen("1k1/3/1P1/1K1 w PB2R2").unwrap();
+
+        assert!(position.is_attacked(4, Color::Black));
+        assert!(!position.is_attacked(3, Color::Black));
+        assert!(position.is_attacked(3, Color::White));
+        assert!(!position.is_attacked(6, Color::White));
+    }
+
+    #[test]
+    fn test_king_field() {
+        let position = parse_sfen("rkb/1p1/1P1/BKR b -").unwrap();
+
+        assert_eq!(position.king_field(Color::White), Some(1));
+        assert_eq!(position.king_field(Color::Black), Some(10));
+    }
+}
@@ -0,0 +1,20 @@
+use crate::game::*;
+
+/// A move of the side to play.
+///
+/// Promotion of a chick reaching the last row is mandatory,
+/// so it is not stored in the move itself.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum Move {
+    Board { from: Field, to: Field },
+    Drop { piece: HandPiece, to: Field },
+}
+
+impl Move {
+    pub fn to(&self) -> Field {
+        match *self {
+            Move::Board { to, .. } => to,
+            Move::Drop { to, .. } => to,
+        }
+    }
+}
@@ -15,18 +15,100 @@ pub enum HandPiece {
 
 pub const ALL_HAND_PIECES: [HandPiece; 3] = [HandPiece::Pawn, HandPiece::Bishop, HandPiece::Rook];
 
+/// A single step a piece can make, as `(row delta, col delta)`.
+///
+/// Steps are given from Black's point of view, where moving forward
+/// means decreasing the row.
+pub type Step = (isize, isize);
+
+const PAWN_STEPS: [Step; 1] = [(-1, 0)];
+const BISHOP_STEPS: [Step; 4] = [(-1, -1), (-1, 1), (1, -1), (1, 1)];
+const ROOK_STEPS: [Step; 4] = [(-1, 0), (0, -1), (0, 1), (1, 0)];
+const KING_STEPS: [Step; 8] = [
+    (-1, -1),
+    (-1, 0),
+    (-1, 1),
+    (0, -1),
+    (0, 1),
+    (1, -1),
+    (1, 0),
+    (1, 1),
+];
+const PROMOTED_PAWN_STEPS: [Step; 6] = [(-1, -1), (-1, 0), (-1, 1), (0, -1), (0, 1), (1, 0)];
+
+impl PieceKind {
+    /// Steps of the piece as seen by Black.
+    pub const fn steps(&self) -> &'static [Step] {
+        match *self {
+            PieceKind::Pawn => &PAWN_STEPS,
+            PieceKind::Bishop => &BISHOP_STEPS,
+            PieceKind::Rook => &ROOK_STEPS,
+            PieceKind::King => &KING_STEPS,
+            PieceKind::PromotedPawn => &PROMOTED_PAWN_STEPS,
+        }
+    }
+}
+
+impl TryFrom<PieceKind> for HandPiece {
+    type Error = ();
+
+    /// Piece that goes to the hand after capturing a piece of the given kind.
+    /// The lion can't be held in hand.
+    fn try_from(kind: PieceKind) -> Result<Self, Self::Error> {
+        match kind {
+            PieceKind::Pawn | PieceKind::PromotedPawn => Ok(HandPiece::Pawn),
+            PieceKind::Bishop => Ok(HandPiece::Bishop),
+            PieceKind::Rook => Ok(HandPiece::Rook),
+            PieceKind::King => Err(()),
+        }
+    }
+}
+
+impl From<HandPiece> for PieceKind {
+    fn from(piece: HandPiece) -> Self {
+        match piece {
+            HandPiece::Pawn => PieceKind::Pawn,
+            HandPiece::Bishop => PieceKind::Bishop,
+            HandPiece::Rook => PieceKind::Rook,
+        }
+    }
+}
+
 #[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
 pub enum Color {
     Black,
     White,
 }
 
+impl Color {
+    pub fn opposite(&self) -> Color {
+        match *self {
+            Color::Black => Color::White,
+            Color::White => Color::Black,
+        }
+    }
+}
+
 #[derive(Debug, Copy, Clone, PartialEq, Eq)]
 pub struct Piece {
     pub kind: PieceKind,
     pub color: Color,
 }
 
+impl Piece {
+    /// Steps of the piece, oriented according to its color.
+    pub fn steps(&self) -> impl Iterator<Item = Step> {
+        let color = self.color;
+        self.kind
+            .steps()
+            .iter()
+            .map(move |&(drow, dcol)| match color {
+                Color::Black => (drow, dcol),
+                Color::White => (-drow, -dcol),
+            })
+    }
+}
+
 pub const WHITE_PAWN: Piece = Piece {
     kind: PieceKind::Pawn,
     color: Color::White,
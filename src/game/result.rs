@@ -0,0 +1,175 @@
+use std::collections::HashMap;
+
+use crate::game::*;
+
+/// Number of occurrences of the same position that ends the game in a draw.
+pub const DEFAULT_REPETITION_LIMIT: usize = 3;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum GameResult {
+    Ongoing,
+    BlackWin,
+    WhiteWin,
+    Draw,
+}
+
+impl GameResult {
+    pub fn win(color: Color) -> Self {
+        match color {
+            Color::Black => GameResult::BlackWin,
+            Color::White => GameResult::WhiteWin,
+        }
+    }
+}
+
+impl Position {
+    /// Result decided by the position alone, without looking at the history.
+    ///
+    /// A side whose lion was captured loses. A side whose lion stands on the
+    /// opponent's back row after its move wins by "try", unless the side to
+    /// play can capture that lion right away. A lion already standing on the
+    /// back row while its side is to play doesn't win, as in a game the try
+    /// is decided right after the move reaching the row.
+    pub fn result(&self) -> GameResult {
+        for color in [Color::Black, Color::White] {
+            if self.king_field(color).is_none() {
+                return GameResult::win(color.opposite());
+            }
+        }
+
+        let mover = self.to_play.opposite();
+        if let Some(field) = self.king_field(mover) {
+            let (row, _) = field2rowcol(field);
+            if row == promotion_row(mover) && !self.is_attacked(field, self.to_play) {
+                return GameResult::win(mover);
+            }
+        }
+
+        GameResult::Ongoing
+    }
+}
+
+impl GameState {
+    pub fn new(position: Position) -> Self {
+        GameState {
+            current_position: position,
+            previous_keys: vec![],
+            key_counts: HashMap::new(),
+            repetition_limit: DEFAULT_REPETITION_LIMIT,
+        }
+    }
+
+    pub fn with_repetition_limit(mut self, repetition_limit: usize) -> Self {
+        self.repetition_limit = repetition_limit;
+        self
+    }
+
+    /// Plays the move, keeping the previous position in the history.
+    pub fn make_move(&mut self, mv: Move) {
+        let key = self.current_position.key();
+        self.previous_keys.push(key);
+        *self.key_counts.entry(key).or_insert(0) += 1;
+        self.current_position.make_move(mv);
+    }
+
+    /// How many times the current position has occurred in the game so far,
+    /// including the current occurrence.
+    pub fn repetitions(&self) -> usize {
+        let key = self.current_position.key();
+        1 + self.key_counts.get(&key).copied().unwrap_or(0)
+    }
+
+    pub fn result(&self) -> GameResult {
+        match self.current_position.result() {
+            GameResult::Ongoing if self.repetitions() >= self.repetition_limit => GameResult::Draw,
+            result => result,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse_sfen;
+
+    fn result_of(sfen: &str) -> GameResult {
+        parse_sfen(sfen).unwrap().result()
+    }
+
+    #[test]
+    fn test_starting_position_is_ongoing() {
+        assert_eq!(result_of("rkb/1p1/1P1/BKR b -"), GameResult::Ongoing);
+    }
+
+    #[test]
+    fn test_lion_capture() {
+        assert_eq!(result_of("1R1/3/3/1K1 w P2B2r"), GameResult::BlackWin);
+        assert_eq!(result_of("1k1/3/3/1r1 b p2b2R"), GameResult::WhiteWin);
+    }
+
+    #[test]
+    fn test_try() {
+        assert_eq!(result_of("K2/3/3/2k w P2B2R2"), GameResult::BlackWin);
+        assert_eq!(result_of("2K/3/3/k2 b p2b2r2"), GameResult::WhiteWin);
+    }
+
+    #[test]
+    fn test_try_refuted_by_capture() {
+        assert_eq!(result_of("Kr1/3/3/2k w P2B2r"), GameResult::Ongoing);
+        assert_eq!(result_of("K2/1b1/3/2k w P2BR2"), GameResult::Ongoing);
+    }
+
+    #[test]
+    fn test_try_only_counts_for_the_side_that_moved() {
+        // Such positions only come from setups. The lion can still win
+        // by moving to a safe field on the back row.
+        assert_eq!(result_of("K2/3/1k1/3 b P2B2R2"), GameResult::Ongoing);
+    }
+
+    #[test]
+    fn test_threefold_repetition() {
+        let mut game = GameState::new(parse_sfen("rkb/1p1/1P1/BKR b -").unwrap());
+        let moves = [
+            Move::Board { from: 10, to: 6 },
+            Move::Board { from: 1, to: 3 },
+            Move::Board { from: 6, to: 10 },
+            Move::Board { from: 3, to: 1 },
+        ];
+
+        for mv in moves {
+            game.make_move(mv);
+            assert_eq!(game.result(), GameResult::Ongoing);
+        }
+        assert_eq!(game.repetitions(), 2);
+
+        for mv in moves {
+            game.make_move(mv);
+        }
+        assert_eq!(game.repetitions(), 3);
+        assert_eq!(game.result(), GameResult::Draw);
+    }
+
+    #[test]
+    fn test_configurable_repetition_limit() {
+        let mut game =
+            GameState::new(parse_sfen("rkb/1p1/1P1/BKR b -").unwrap()).with_repetition_limit(4);
+        let moves = [
+            Move::Board { from: 10, to: 6 },
+            Move::Board { from: 1, to: 3 },
+            Move::Board { from: 6, to: 10 },
+            Move::Board { from: 3, to: 1 },
+        ];
+
+        for _ in 0..2 {
+            for mv in moves {
+                game.make_move(mv);
+            }
+        }
+        assert_eq!(game.result(), GameResult::Ongoing);
+
+        for mv in moves {
+            game.make_move(mv);
+        }
+        assert_eq!(game.result(), GameResult::Draw);
+    }
+}
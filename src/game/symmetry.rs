@@ -0,0 +1,218 @@
+use crate::game::*;
+
+/// Field in the same row, on the other side of the middle column.
+pub(crate) const fn mirror_field(field: Field) -> Field {
+    let (row, col) = (field / COLS, field % COLS);
+    row * COLS + COLS - 1 - col
+}
+
+/// Field after rotating the board by 180 degrees.
+pub(crate) const fn rotate_field(field: Field) -> Field {
+    BOARD_SIZE - 1 - field
+}
+
+impl Board {
+    /// The board reflected across the middle column.
+    pub fn mirrored(&self) -> Self {
+        let mut mirrored = Board::empty();
+        for field in ALL_INDEXES {
+            mirrored[mirror_field(field)] = self[field];
+        }
+        mirrored
+    }
+
+    /// The board rotated by 180 degrees with the colors of all pieces swapped.
+    pub fn flipped(&self) -> Self {
+        let mut flipped = Board::empty();
+        for field in ALL_INDEXES {
+            flipped[rotate_field(field)] = self[field].map(|piece| Piece {
+                color: piece.color.opposite(),
+                ..piece
+            });
+        }
+        flipped
+    }
+}
+
+impl Move {
+    /// The same move in the mirrored position.
+    pub fn mirrored(&self) -> Self {
+        match *self {
+            Move::Board { from, to } => Move::Board {
+                from: mirror_field(from),
+                to: mirror_field(to),
+            },
+            Move::Drop { piece, to } => Move::Drop {
+                piece,
+                to: mirror_field(to),
+            },
+        }
+    }
+
+    /// The same move in the flipped position.
+    pub fn flipped(&self) -> Self {
+        match *self {
+            Move::Board { from, to } => Move::Board {
+                from: rotate_field(from),
+                to: rotate_field(to),
+            },
+            Move::Drop { piece, to } => Move::Drop {
+                piece,
+                to: rotate_field(to),
+            },
+        }
+    }
+}
+
+impl Position {
+    /// The position reflected across the middle column.
+    pub fn mirrored(&self) -> Self {
+        Position::new(
+            self.board.mirrored(),
+            self.to_play,
+            self.black_hand,
+            self.white_hand,
+        )
+        .with_optional_move_number(self.move_number)
+    }
+
+    /// The position seen by the other player: the board is rotated
+    /// by 180 degrees, the colors and hands are swapped and the other
+    /// side is to play. The move number is kept, so that flipping twice
+    /// gives back the same position.
+    pub fn flipped(&self) -> Self {
+        Position::new(
+            self.board.flipped(),
+            self.to_play.opposite(),
+            self.white_hand,
+            self.black_hand,
+        )
+        .with_optional_move_number(self.move_number)
+    }
+
+    /// Representative of the positions equivalent under the symmetries:
+    /// flipped so that Black is to play, then either itself or its mirror
+    /// image, whichever has the smaller Zobrist key.
+    pub fn canonical(&self) -> Self {
+        let position = match self.to_play {
+            Color::Black => *self,
+            Color::White => self.flipped(),
+        };
+        let mirrored = position.mirrored();
+        if mirrored.key < position.key {
+            mirrored
+        } else {
+            position
+        }
+    }
+
+    fn with_optional_move_number(mut self, move_number: Option<u32>) -> Self {
+        self.move_number = move_number;
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::eval::evaluate;
+    use crate::parser::parse_sfen;
+    use crate::perft::perft;
+
+    const SFENS: [&str; 4] = [
+        "r1b/1k1/3/BPK b Pr",
+        "1k1/p2/3/1K1 w PBRbr",
+        "k2/1P1/3/2K b BRbpr",
+        "2k/1p+1/R2/K1b w PBr",
+    ];
+
+    #[test]
+    fn test_mirrored_startpos() {
+        let position = Position::startpos().mirrored();
+
+        assert_eq!(position, parse_sfen("bkr/1p1/1P1/RKB b -").unwrap());
+    }
+
+    #[test]
+    fn test_flipped_startpos() {
+        let position = Position::startpos().flipped();
+
+        assert_eq!(position, parse_sfen("rkb/1p1/1P1/BKR w -").unwrap());
+    }
+
+    #[test]
+    fn test_flipped_position() {
+        let position = parse_sfen("1k1/p2/3/1K1 w PBRbr 5").unwrap();
+
+        assert_eq!(
+            position.flipped(),
+            parse_sfen("1k1/3/2P/1K1 b BRpbr 5").unwrap()
+        );
+        assert_eq!(
+            Position::startpos().with_move_number(1).flipped(),
+            parse_sfen("rkb/1p1/1P1/BKR w - 1").unwrap()
+        );
+    }
+
+    #[test]
+    fn test_symmetries_are_involutions() {
+        for sfen in SFENS {
+            let position = parse_sfen(sfen).unwrap();
+
+            assert_eq!(position.mirrored().mirrored(), position, "{}", sfen);
+            assert_eq!(position.flipped().flipped(), position, "{}", sfen);
+
+            let numbered = position.with_move_number(7);
+            assert_eq!(numbered.mirrored().mirrored(), numbered, "{}", sfen);
+            assert_eq!(numbered.flipped().flipped(), numbered, "{}", sfen);
+        }
+    }
+
+    #[test]
+    fn test_symmetries_keep_the_game() {
+        for sfen in SFENS {
+            let mut position = parse_sfen(sfen).unwrap();
+            let nodes = perft(&mut position, 3);
+
+            assert_eq!(perft(&mut position.mirrored(), 3), nodes, "{}", sfen);
+            assert_eq!(perft(&mut position.flipped(), 3), nodes, "{}", sfen);
+            assert_eq!(evaluate(&position.flipped()), evaluate(&position));
+        }
+    }
+
+    #[test]
+    fn test_moves_follow_the_position() {
+        for sfen in SFENS {
+            let position = parse_sfen(sfen).unwrap();
+            for mv in position.legal_moves() {
+                let mut after = position;
+                after.make_move(mv);
+
+                let mut mirrored = position.mirrored();
+                mirrored.make_move(mv.mirrored());
+                assert_eq!(mirrored, after.mirrored(), "{} {:?}", sfen, mv);
+
+                let mut flipped = position.flipped();
+                flipped.make_move(mv.flipped());
+                assert_eq!(flipped, after.flipped(), "{} {:?}", sfen, mv);
+            }
+        }
+    }
+
+    #[test]
+    fn test_canonical() {
+        for sfen in SFENS {
+            let position = parse_sfen(sfen).unwrap();
+            let canonical = position.canonical();
+
+            assert_eq!(canonical.to_play, Color::Black);
+            for equivalent in [
+                position.mirrored(),
+                position.flipped(),
+                position.flipped().mirrored(),
+            ] {
+                assert_eq!(equivalent.canonical(), canonical, "{}", sfen);
+            }
+        }
+    }
+}
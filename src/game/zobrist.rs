@@ -0,0 +1,179 @@
+use crate::game::*;
+
+/// Most pieces of a single kind a hand can hold.
+pub const MAX_HAND_COUNT: usize = 2;
+
+const PIECE_KINDS: usize = 5;
+const COLORS: usize = 2;
+const HAND_PIECES: usize = 3;
+/// Hand counts with a key, from an empty hand to a full one.
+const HAND_COUNTS: usize = MAX_HAND_COUNT + 1;
+
+struct Keys {
+    board: [[u64; PIECE_KINDS * COLORS]; BOARD_SIZE],
+    hand: [[[u64; HAND_COUNTS]; HAND_PIECES]; COLORS],
+    white_to_play: u64,
+}
+
+const fn splitmix64(state: u64) -> (u64, u64) {
+    let state = state.wrapping_add(0x9e3779b97f4a7c15);
+    let mut z = state;
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+    (state, z ^ (z >> 31))
+}
+
+const fn generate_keys() -> Keys {
+    let mut keys = Keys {
+        board: [[0; PIECE_KINDS * COLORS]; BOARD_SIZE],
+        hand: [[[0; HAND_COUNTS]; HAND_PIECES]; COLORS],
+        white_to_play: 0,
+    };
+    let mut state = 0x6c65_7765_6b00_0000;
+
+    let mut field = 0;
+    while field < BOARD_SIZE {
+        let mut piece = 0;
+        while piece < PIECE_KINDS * COLORS {
+            let (next, key) = splitmix64(state);
+            state = next;
+            keys.board[field][piece] = key;
+            piece += 1;
+        }
+        field += 1;
+    }
+
+    let mut color = 0;
+    while color < COLORS {
+        let mut piece = 0;
+        while piece < HAND_PIECES {
+            let mut count = 0;
+            while count < HAND_COUNTS {
+                let (next, key) = splitmix64(state);
+                state = next;
+                keys.hand[color][piece][count] = key;
+                count += 1;
+            }
+            piece += 1;
+        }
+        color += 1;
+    }
+
+    let (_, key) = splitmix64(state);
+    keys.white_to_play = key;
+    keys
+}
+
+static KEYS: Keys = generate_keys();
+
+/// Key of a piece standing on a field.
+pub fn piece_key(field: Field, piece: Piece) -> u64 {
+    KEYS.board[field][piece.kind as usize * COLORS + piece.color as usize]
+}
+
+/// Key of a hand holding `count` pieces of a kind.
+pub fn hand_key(color: Color, piece: HandPiece, count: usize) -> u64 {
+    KEYS.hand[color as usize][piece as usize][count]
+}
+
+pub fn to_play_key(color: Color) -> u64 {
+    match color {
+        Color::Black => 0,
+        Color::White => KEYS.white_to_play,
+    }
+}
+
+impl Position {
+    /// Computes the Zobrist key of the position from scratch.
+    pub fn compute_key(&self) -> u64 {
+        let mut key = to_play_key(self.to_play);
+        for field in ALL_INDEXES {
+            if let Some(piece) = self.board[field] {
+                key ^= piece_key(field, piece);
+            }
+        }
+        for color in [Color::Black, Color::White] {
+            for piece in ALL_HAND_PIECES {
+                key ^= hand_key(color, piece, self.hand(color).count(piece));
+            }
+        }
+        key
+    }
+
+    /// Zobrist key of the position, kept up to date by
+    /// [`Position::make_move`] and [`Position::unmake_move`].
+    pub fn key(&self) -> u64 {
+        self.key
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use super::*;
+    use crate::parser::parse_sfen;
+
+    #[test]
+    fn test_keys_are_distinct() {
+        let mut keys = HashSet::new();
+        for row in KEYS.board.iter() {
+            keys.extend(row.iter().copied());
+        }
+        for color in KEYS.hand.iter() {
+            for piece in color.iter() {
+                keys.extend(piece.iter().copied());
+            }
+        }
+        keys.insert(KEYS.white_to_play);
+
+        assert_eq!(keys.len(), BOARD_SIZE * 10 + 2 * 3 * HAND_COUNTS + 1);
+        assert!(!keys.contains(&0));
+    }
+
+    #[test]
+    fn test_key_depends_on_everything() {
+        let sfens = [
+            "rkb/1p1/1P1/BKR b -",
+            "rkb/1p1/1P1/BKR w -",
+            "rkb/1p1/1P1/KBR b -",
+            "rkb/1p1/1P+1/BKR b -",
+            "rkb/1p1/3/BKR b P",
+            "rkb/1p1/3/BKR b p",
+            "rkb/3/3/BKR b P2",
+            "rkb/3/3/BKR b Pp",
+        ];
+
+        let keys: HashSet<u64> = sfens
+            .iter()
+            .map(|sfen| parse_sfen(sfen).unwrap().key())
+            .collect();
+
+        assert_eq!(keys.len(), sfens.len());
+    }
+
+    #[test]
+    fn test_incremental_update() {
+        let sfens = [
+            "rkb/1p1/1P1/BKR b -",
+            "1k1/3/1p+1/1R1 b B2pr",
+            "k2/1P1/3/2K b PB2R2",
+            "k2/3/1p1/2K w pb2r2",
+            "r1b/1k1/1P1/B1K b Pr",
+            "1k1/p2/3/1K1 w PBRbr",
+        ];
+
+        for sfen in sfens {
+            let mut position = parse_sfen(sfen).unwrap();
+            let original_key = position.key();
+            assert_eq!(original_key, position.compute_key());
+
+            for mv in position.legal_moves() {
+                let undo = position.make_move(mv);
+                assert_eq!(position.key(), position.compute_key(), "{} {:?}", sfen, mv);
+                position.unmake_move(mv, undo);
+                assert_eq!(position.key(), original_key, "{} {:?}", sfen, mv);
+            }
+        }
+    }
+}
@@ -0,0 +1,539 @@
+use std::fmt;
+use std::str::FromStr;
+
+use crate::game::*;
+use crate::parser::{parse_sfen, SfenError};
+use crate::sfen::Sfen;
+
+/// Header holding the SFEN of the starting position when it is not the usual one.
+const START_HEADER: &str = "開始局面";
+/// Header naming the handicap, only "平手" (no handicap) is known.
+const HANDICAP_HEADER: &str = "手合割";
+const NO_HANDICAP: &str = "平手";
+
+const FILES: [char; COLS] = ['１', '２', '３'];
+const RANKS: [char; ROWS] = ['一', '二', '三', '四'];
+
+const RESIGN: &str = "投了";
+const FOUL_WIN: &str = "反則勝ち";
+const REPETITION: &str = "千日手";
+const INTERRUPT: &str = "中断";
+
+/// Japanese name of the piece, the same for both colors.
+pub fn kif_name(kind: PieceKind) -> &'static str {
+    match kind {
+        PieceKind::Pawn => "ひよこ",
+        PieceKind::Bishop => "ぞう",
+        PieceKind::Rook => "きりん",
+        PieceKind::King => "ライオン",
+        PieceKind::PromotedPawn => "にわとり",
+    }
+}
+
+fn side_name(color: Color) -> &'static str {
+    match color {
+        Color::Black => "先手",
+        Color::White => "後手",
+    }
+}
+
+/// Reason why a KIF record could not be read. Lines are numbered from 1.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum KifError {
+    Syntax { line: usize, expected: &'static str },
+    Sfen { line: usize, error: SfenError },
+    IllegalMove { line: usize, mv: Move },
+}
+
+impl fmt::Display for KifError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            KifError::Syntax { line, expected } => {
+                write!(f, "invalid record at line {}: expected {}", line, expected)
+            }
+            KifError::Sfen { line, error } => {
+                write!(f, "invalid starting position at line {}: {}", line, error)
+            }
+            KifError::IllegalMove { line, mv } => {
+                write!(f, "illegal move {} at line {}", mv, line)
+            }
+        }
+    }
+}
+
+impl std::error::Error for KifError {}
+
+/// A game record in the KIF notation.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Kif {
+    /// Metadata such as the players ("先手", "後手") or the date ("開始日時"),
+    /// in the order they appear in the record.
+    pub headers: Vec<(String, String)>,
+    pub start: Position,
+    pub moves: Vec<Move>,
+    pub result: GameResult,
+}
+
+impl Kif {
+    pub fn new(start: Position) -> Self {
+        Kif {
+            headers: vec![],
+            start,
+            moves: vec![],
+            result: GameResult::Ongoing,
+        }
+    }
+
+    /// Record of the moves played from `start`, with the result they led to.
+    pub fn from_moves(start: Position, moves: &[Move]) -> Self {
+        let mut kif = Kif {
+            moves: moves.to_vec(),
+            ..Kif::new(start)
+        };
+        kif.result = kif.game_state().result();
+        kif
+    }
+
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(header, _)| header == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    pub fn set_header(&mut self, name: &str, value: &str) {
+        match self.headers.iter_mut().find(|(header, _)| header == name) {
+            Some((_, old)) => *old = value.to_owned(),
+            None => self.headers.push((name.to_owned(), value.to_owned())),
+        }
+    }
+
+    /// The game after all the moves of the record.
+    pub fn game_state(&self) -> GameState {
+        let mut game = GameState::new(self.start);
+        for &mv in &self.moves {
+            game.make_move(mv);
+        }
+        game
+    }
+
+    pub fn position(&self) -> Position {
+        self.game_state().current_position
+    }
+}
+
+fn write_field(field: Field) -> String {
+    let (row, col) = field2rowcol(field);
+    format!("{}{}", FILES[COLS - 1 - col], RANKS[row])
+}
+
+/// Notation of the move in the position, `previous` being the destination
+/// of the previous move.
+fn write_move(position: &Position, mv: Move, previous: Option<Field>) -> String {
+    let to = if previous == Some(mv.to()) {
+        "同　".to_owned()
+    } else {
+        write_field(mv.to())
+    };
+    match mv {
+        Move::Board { from, .. } => {
+            let piece = position.board()[from].expect("a piece on the starting field");
+            let (row, col) = field2rowcol(from);
+            format!(
+                "{}{}{}({}{})",
+                to,
+                kif_name(piece.kind),
+                if position.is_promotion(mv) { "成" } else { "" },
+                COLS - col,
+                row + 1
+            )
+        }
+        Move::Drop { piece, .. } => format!("{}{}打", to, kif_name(piece.into())),
+    }
+}
+
+impl fmt::Display for Kif {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (name, value) in &self.headers {
+            writeln!(f, "{}：{}", name, value)?;
+        }
+        if self.start == Position::startpos() {
+            writeln!(f, "{}：{}", HANDICAP_HEADER, NO_HANDICAP)?;
+        } else {
+            writeln!(f, "{}：{}", START_HEADER, self.start.sfen())?;
+        }
+        writeln!(f, "手数----指手---------消費時間--")?;
+
+        let mut game = GameState::new(self.start);
+        let mut previous = None;
+        for (ply, &mv) in self.moves.iter().enumerate() {
+            writeln!(
+                f,
+                "{:>4} {}",
+                ply + 1,
+                write_move(&game.current_position, mv, previous)
+            )?;
+            previous = Some(mv.to());
+            game.make_move(mv);
+        }
+
+        let plies = self.moves.len();
+        let to_play = game.current_position.to_play();
+        if game.result() != self.result {
+            let termination = match self.result {
+                GameResult::Ongoing => INTERRUPT,
+                GameResult::Draw => REPETITION,
+                result if result == GameResult::win(to_play) => FOUL_WIN,
+                _ => RESIGN,
+            };
+            writeln!(f, "{:>4} {}", plies + 1, termination)?;
+        }
+
+        match self.result {
+            GameResult::Ongoing => writeln!(f, "まで{}手で{}", plies, INTERRUPT),
+            GameResult::Draw => writeln!(f, "まで{}手で{}", plies, REPETITION),
+            GameResult::BlackWin => {
+                writeln!(f, "まで{}手で{}の勝ち", plies, side_name(Color::Black))
+            }
+            GameResult::WhiteWin => {
+                writeln!(f, "まで{}手で{}の勝ち", plies, side_name(Color::White))
+            }
+        }
+    }
+}
+
+fn parse_field(file: char, rank: char) -> Option<Field> {
+    let col = COLS - 1 - FILES.iter().position(|&c| c == file)?;
+    let row = RANKS.iter().position(|&c| c == rank)?;
+    Some(rowcol2field(row, col))
+}
+
+fn parse_piece_name(text: &str) -> Option<(PieceKind, &str)> {
+    ALL_PIECE_KINDS
+        .into_iter()
+        .find_map(|kind| text.strip_prefix(kif_name(kind)).map(|rest| (kind, rest)))
+}
+
+/// Parses the move notation at the start of `text`, as played in the position.
+fn parse_move(
+    text: &str,
+    position: &Position,
+    previous: Option<Field>,
+    line: usize,
+) -> Result<Move, KifError> {
+    let syntax = |expected| KifError::Syntax { line, expected };
+
+    let (to, rest) = if let Some(rest) = text.strip_prefix('同') {
+        let to = previous.ok_or(syntax("a destination field"))?;
+        (to, rest.trim_start_matches([' ', '　']))
+    } else {
+        let mut chars = text.chars();
+        let (Some(file), Some(rank)) = (chars.next(), chars.next()) else {
+            return Err(syntax("a destination field"));
+        };
+        let to = parse_field(file, rank).ok_or(syntax("a destination field"))?;
+        (to, chars.as_str())
+    };
+
+    let (kind, rest) = parse_piece_name(rest).ok_or(syntax("a piece name"))?;
+    let (promotion, rest) = match rest.strip_prefix('成') {
+        Some(rest) => (true, rest),
+        None => (false, rest),
+    };
+
+    let mv = if rest.starts_with('打') {
+        let piece = HandPiece::try_from(kind)
+            .ok()
+            .filter(|_| kind != PieceKind::PromotedPawn)
+            .ok_or(syntax("a piece which can be dropped"))?;
+        Move::Drop { piece, to }
+    } else {
+        let bytes = rest.as_bytes();
+        let from = match bytes {
+            [b'(', file @ b'1'..=b'3', rank @ b'1'..=b'4', b')', ..] => {
+                rowcol2field((rank - b'1') as Row, COLS - (file - b'0') as Col)
+            }
+            _ => return Err(syntax("the starting field in parentheses")),
+        };
+        if position.board()[from].map(|piece| piece.kind) != Some(kind) {
+            return Err(syntax("the name of the piece on the starting field"));
+        }
+        Move::Board { from, to }
+    };
+
+    if !position.legal_moves().contains(&mv) {
+        return Err(KifError::IllegalMove { line, mv });
+    }
+    if promotion && !position.is_promotion(mv) {
+        return Err(syntax("'成' only on promoting moves"));
+    }
+    Ok(mv)
+}
+
+impl FromStr for Kif {
+    type Err = KifError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut kif = Kif::new(Position::startpos());
+        let mut game = GameState::new(kif.start);
+        let mut previous = None;
+        let mut termination = None;
+        let mut summary = None;
+
+        for (index, text) in s.lines().enumerate() {
+            let line = index + 1;
+            let text = text.trim();
+            let syntax = |expected| KifError::Syntax { line, expected };
+
+            if text.is_empty() || text.starts_with(['#', '*']) || text.starts_with("手数") {
+                continue;
+            }
+
+            if text.starts_with(|c: char| c.is_ascii_digit()) {
+                let (number, rest) = text.split_at(
+                    text.find(|c: char| !c.is_ascii_digit())
+                        .unwrap_or(text.len()),
+                );
+                let rest = rest.trim_start();
+                if termination.is_some() || number.parse() != Ok(kif.moves.len() + 1) {
+                    return Err(syntax("moves numbered from 1"));
+                }
+
+                let token = rest.split_whitespace().next().unwrap_or("");
+                termination = match token {
+                    RESIGN => Some(GameResult::win(game.current_position.to_play().opposite())),
+                    FOUL_WIN => Some(GameResult::win(game.current_position.to_play())),
+                    REPETITION => Some(GameResult::Draw),
+                    INTERRUPT => Some(GameResult::Ongoing),
+                    _ => None,
+                };
+                if termination.is_some() {
+                    continue;
+                }
+
+                if game.result() != GameResult::Ongoing {
+                    return Err(syntax("no moves after the end of the game"));
+                }
+                let mv = parse_move(rest, &game.current_position, previous, line)?;
+                kif.moves.push(mv);
+                game.make_move(mv);
+                previous = Some(mv.to());
+                continue;
+            }
+
+            if let Some(rest) = text.strip_prefix("まで") {
+                summary = Some(if rest.contains("先手の勝ち") {
+                    GameResult::BlackWin
+                } else if rest.contains("後手の勝ち") {
+                    GameResult::WhiteWin
+                } else if rest.contains(REPETITION) {
+                    GameResult::Draw
+                } else {
+                    GameResult::Ongoing
+                });
+                continue;
+            }
+
+            let Some((name, value)) = text.split_once(['：', ':']) else {
+                return Err(syntax("a header, a move or a result"));
+            };
+            if !kif.moves.is_empty() {
+                return Err(syntax("headers before the moves"));
+            }
+            let (name, value) = (name.trim(), value.trim());
+            match name {
+                START_HEADER => {
+                    kif.start =
+                        parse_sfen(value).map_err(|error| KifError::Sfen { line, error })?;
+                    game = GameState::new(kif.start);
+                }
+                HANDICAP_HEADER if value == NO_HANDICAP => {}
+                HANDICAP_HEADER => return Err(syntax("no handicap")),
+                _ => kif.set_header(name, value),
+            }
+        }
+
+        kif.result = termination.or(summary).unwrap_or_else(|| game.result());
+        Ok(kif)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn moves(position: &Position, notation: &[&str]) -> Vec<Move> {
+        let mut position = *position;
+        notation
+            .iter()
+            .map(|text| {
+                let mv = position.parse_move(text).unwrap();
+                position.make_move(mv);
+                mv
+            })
+            .collect()
+    }
+
+    const GAME: &str = "\
+先手：Alice
+後手：Bob
+手合割：平手
+手数----指手---------消費時間--
+   1 ２二ひよこ(23)
+   2 同　ぞう(11)
+   3 １二ひよこ打
+   4 １三ぞう(22)
+   5 １一ひよこ成(12)
+   6 ２四ぞう(13)
+まで6手で後手の勝ち
+";
+
+    #[test]
+    fn test_write_game() {
+        let start = Position::startpos();
+        let played = moves(&start, &["b3b2", "c1b2", "P*c2", "b2c3", "c2c1+", "c3b4"]);
+        let mut kif = Kif::from_moves(start, &played);
+        kif.set_header("先手", "Alice");
+        kif.set_header("後手", "Bob");
+
+        assert_eq!(kif.result, GameResult::WhiteWin);
+        assert_eq!(kif.to_string(), GAME);
+    }
+
+    #[test]
+    fn test_read_game() {
+        let kif: Kif = GAME.parse().unwrap();
+
+        assert_eq!(kif.header("先手"), Some("Alice"));
+        assert_eq!(kif.header("後手"), Some("Bob"));
+        assert_eq!(kif.start, Position::startpos());
+        assert_eq!(kif.moves.len(), 6);
+        assert_eq!(kif.moves[2].to_string(), "P*c2");
+        assert_eq!(kif.result, GameResult::WhiteWin);
+        assert_eq!(kif.game_state().result(), GameResult::WhiteWin);
+    }
+
+    #[test]
+    fn test_custom_start_and_promotion() {
+        let start = parse_sfen("1k1/P2/3/1K1 b pb2r2 5").unwrap();
+        let kif = Kif::from_moves(start, &moves(&start, &["a2a1+", "b1a1"]));
+        let text = kif.to_string();
+
+        assert!(text.contains("開始局面：1k1/P2/3/1K1 b pb2r2 5\n"));
+        assert!(text.contains("   1 ３一ひよこ成(32)\n"));
+        assert!(text.contains("   2 同　ライオン(21)\n"));
+
+        let read: Kif = text.parse().unwrap();
+        assert_eq!(read.start, start);
+        assert_eq!(read.moves, kif.moves);
+    }
+
+    #[test]
+    fn test_terminations() {
+        let start = Position::startpos();
+        let played = moves(&start, &["b3b2", "c1b2"]);
+        let cases = [
+            (GameResult::WhiteWin, "   3 投了\nまで2手で後手の勝ち\n"),
+            (GameResult::BlackWin, "   3 反則勝ち\nまで2手で先手の勝ち\n"),
+            (GameResult::Draw, "   3 千日手\nまで2手で千日手\n"),
+            (GameResult::Ongoing, "まで2手で中断\n"),
+        ];
+
+        for (result, ending) in cases {
+            let kif = Kif {
+                result,
+                ..Kif::from_moves(start, &played)
+            };
+            let text = kif.to_string();
+
+            assert!(text.ends_with(ending), "{}", text);
+            assert_eq!(text.parse::<Kif>(), Ok(kif));
+        }
+    }
+
+    #[test]
+    fn test_times_and_comments_are_ignored() {
+        let text = "\
+# comment
+開始日時：2026/10/17
+手数----指手---------消費時間--
+   1 ２二ひよこ(23)   ( 0:05/00:00:05)
+*a comment to the move
+   2 同　ぞう(11)   ( 0:03/00:00:03)
+";
+        let kif: Kif = text.parse().unwrap();
+
+        assert_eq!(kif.header("開始日時"), Some("2026/10/17"));
+        assert_eq!(kif.moves.len(), 2);
+        assert_eq!(kif.result, GameResult::Ongoing);
+    }
+
+    #[test]
+    fn test_errors() {
+        let cases = [
+            (
+                "   1 ２二ひよこ(24)\n",
+                KifError::Syntax {
+                    line: 1,
+                    expected: "the name of the piece on the starting field",
+                },
+            ),
+            (
+                "   1 ２一ライオン(24)\n",
+                KifError::IllegalMove {
+                    line: 1,
+                    mv: Move::Board { from: 10, to: 1 },
+                },
+            ),
+            (
+                "   2 ２二ひよこ(23)\n",
+                KifError::Syntax {
+                    line: 1,
+                    expected: "moves numbered from 1",
+                },
+            ),
+            (
+                "   1 同　ひよこ(23)\n",
+                KifError::Syntax {
+                    line: 1,
+                    expected: "a destination field",
+                },
+            ),
+            (
+                "   1 ２二ひよこ\n",
+                KifError::Syntax {
+                    line: 1,
+                    expected: "the starting field in parentheses",
+                },
+            ),
+            (
+                "   1 ２二ひよこ成(23)\n",
+                KifError::Syntax {
+                    line: 1,
+                    expected: "'成' only on promoting moves",
+                },
+            ),
+            (
+                "   1 ２二ライオン打\n",
+                KifError::Syntax {
+                    line: 1,
+                    expected: "a piece which can be dropped",
+                },
+            ),
+            (
+                "何か\n",
+                KifError::Syntax {
+                    line: 1,
+                    expected: "a header, a move or a result",
+                },
+            ),
+        ];
+
+        for (text, error) in cases {
+            assert_eq!(text.parse::<Kif>(), Err(error), "{}", text);
+        }
+
+        let error = "開始局面：rkb/1p1/1P1/BKR x -".parse::<Kif>().unwrap_err();
+        assert!(matches!(error, KifError::Sfen { line: 1, .. }));
+    }
+}
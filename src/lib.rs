@@ -1,3 +1,36 @@
+mod csa;
+mod eval;
 mod game;
+mod kif;
+mod notation;
 mod parser;
+mod perft;
+mod record;
+mod render;
+mod search;
 mod sfen;
+mod solver;
+mod tt;
+
+pub use csa::{csa_code, Csa, CsaError, CsaMove, Termination};
+pub use eval::{evaluate, evaluate_with, Weights, WeightsError};
+pub use game::{
+    attacks, field2rowcol, fields, promotion_row, rowcol2field, step_field, BitBoard, Board, Col,
+    Color, Field, GameResult, GameState, Hand, HandPiece, IndexMode, Mask, Move, Piece, PieceKind,
+    Position, Row, Step, Undo, ALL_HAND_PIECES, ALL_INDEXES, ALL_PIECES, ALL_PIECE_KINDS,
+    BLACK_BISHOP, BLACK_KING, BLACK_PAWN, BLACK_PROMOTED_PAWN, BLACK_ROOK, BOARD_SIZE, COLS,
+    DEFAULT_REPETITION_LIMIT, FULL_BOARD, MAX_HAND_COUNT, ROWS, WHITE_BISHOP, WHITE_KING,
+    WHITE_PAWN, WHITE_PROMOTED_PAWN, WHITE_ROOK,
+};
+pub use kif::{kif_name, Kif, KifError};
+pub use notation::{field_name, MoveError};
+pub use parser::{SfenError, SfenMode};
+pub use perft::{divide, perft};
+pub use record::{GameRecord, RecordError};
+pub use render::{Glyphs, Renderer};
+pub use search::{
+    search, Score, SearchLimits, SearchResult, Searcher, MAX_DEPTH, WIN_SCORE, WIN_THRESHOLD,
+};
+pub use sfen::Sfen;
+pub use solver::{Database, Outcome, Progress, Solution};
+pub use tt::{Bound, TranspositionTable, TtEntry, DEFAULT_TT_SIZE_MB};
@@ -0,0 +1,276 @@
+use std::fmt;
+use std::str::FromStr;
+
+use crate::game::*;
+use crate::sfen::Sfen;
+
+/// Reason why a move could not be parsed or played.
+///
+/// Offsets are byte offsets into the move string.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MoveError {
+    Syntax {
+        offset: usize,
+        expected: &'static str,
+    },
+    /// The move is well formed, but can't be played in the position.
+    Illegal { mv: Move },
+    /// The move is marked as a promotion, but it does not promote.
+    Promotion { mv: Move },
+    /// The game has already ended, so no more moves can be played.
+    GameOver { mv: Move },
+}
+
+impl fmt::Display for MoveError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MoveError::Syntax { offset, expected } => {
+                write!(f, "invalid move at byte {}: expected {}", offset, expected)
+            }
+            MoveError::Illegal { mv } => write!(f, "illegal move {}", mv),
+            MoveError::Promotion { mv } => write!(f, "move {} does not promote", mv),
+            MoveError::GameOver { mv } => write!(f, "move {} after the end of the game", mv),
+        }
+    }
+}
+
+impl std::error::Error for MoveError {}
+
+/// Name of the field, the file letter `a`-`c` from the left
+/// followed by the rank `1`-`4` from the top.
+pub fn field_name(field: Field) -> String {
+    let (row, col) = field2rowcol(field);
+    format!("{}{}", (b'a' + col as u8) as char, row + 1)
+}
+
+fn parse_field(text: &[u8], offset: usize) -> Result<Field, MoveError> {
+    let expected = "a field from a1 to c4";
+    match text.get(offset..offset + 2) {
+        Some(&[file @ b'a'..=b'c', rank @ b'1'..=b'4']) => {
+            Ok(rowcol2field((rank - b'1') as Row, (file - b'a') as Col))
+        }
+        _ => Err(MoveError::Syntax { offset, expected }),
+    }
+}
+
+/// Parses a move, returning it together with its promotion mark.
+fn parse_move(text: &str) -> Result<(Move, bool), MoveError> {
+    let bytes = text.as_bytes();
+    let (mv, end) = match bytes {
+        [piece, b'*', ..] => {
+            let piece = match piece {
+                b'P' => HandPiece::Pawn,
+                b'B' => HandPiece::Bishop,
+                b'R' => HandPiece::Rook,
+                _ => {
+                    return Err(MoveError::Syntax {
+                        offset: 0,
+                        expected: "one of 'P', 'B' or 'R' to drop",
+                    })
+                }
+            };
+            let to = parse_field(bytes, 2)?;
+            (Move::Drop { piece, to }, 4)
+        }
+        _ => {
+            let from = parse_field(bytes, 0)?;
+            let to = parse_field(bytes, 2)?;
+            (Move::Board { from, to }, 4)
+        }
+    };
+
+    match &bytes[end..] {
+        [] => Ok((mv, false)),
+        [b'+'] if matches!(mv, Move::Board { .. }) => Ok((mv, true)),
+        _ => Err(MoveError::Syntax {
+            offset: end,
+            expected: "end of the move",
+        }),
+    }
+}
+
+/// Renders the move without the promotion mark, which depends on the position.
+impl fmt::Display for Move {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            Move::Board { from, to } => write!(f, "{}{}", field_name(from), field_name(to)),
+            Move::Drop { piece, to } => write!(
+                f,
+                "{}*{}",
+                piece.sfen().to_ascii_uppercase(),
+                field_name(to)
+            ),
+        }
+    }
+}
+
+/// Parses a move, ignoring the promotion mark. Use [`Position::parse_move`]
+/// to check that the move can be played.
+impl FromStr for Move {
+    type Err = MoveError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        parse_move(s).map(|(mv, _)| mv)
+    }
+}
+
+impl Position {
+    /// Whether the move promotes a chick.
+    pub fn is_promotion(&self, mv: Move) -> bool {
+        match mv {
+            Move::Board { from, to } => {
+                self.board()[from].is_some_and(|piece| piece.kind == PieceKind::Pawn)
+                    && field2rowcol(to).0 == promotion_row(self.to_play())
+            }
+            Move::Drop { .. } => false,
+        }
+    }
+
+    /// Parses a move and checks that it is legal in the position.
+    ///
+    /// The promotion mark is optional, as promotion is mandatory,
+    /// but it is rejected on moves which don't promote.
+    pub fn parse_move(&self, text: &str) -> Result<Move, MoveError> {
+        let (mv, promotion) = parse_move(text)?;
+        if !self.legal_moves().contains(&mv) {
+            return Err(MoveError::Illegal { mv });
+        }
+        if promotion && !self.is_promotion(mv) {
+            return Err(MoveError::Promotion { mv });
+        }
+        Ok(mv)
+    }
+
+    /// Notation of the move, with the promotion mark if it promotes.
+    pub fn move_notation(&self, mv: Move) -> String {
+        if self.is_promotion(mv) {
+            format!("{}+", mv)
+        } else {
+            mv.to_string()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse_sfen;
+
+    #[test]
+    fn test_field_names() {
+        assert_eq!(field_name(0), "a1");
+        assert_eq!(field_name(7), "b3");
+        assert_eq!(field_name(11), "c4");
+    }
+
+    #[test]
+    fn test_display() {
+        assert_eq!(Move::Board { from: 7, to: 4 }.to_string(), "b3b2");
+        assert_eq!(Move::Board { from: 10, to: 6 }.to_string(), "b4a3");
+        let drop = Move::Drop {
+            piece: HandPiece::Pawn,
+            to: 4,
+        };
+        assert_eq!(drop.to_string(), "P*b2");
+    }
+
+    #[test]
+    fn test_from_str() {
+        assert_eq!("b3b2".parse(), Ok(Move::Board { from: 7, to: 4 }));
+        assert_eq!("b2b1+".parse(), Ok(Move::Board { from: 4, to: 1 }));
+        assert_eq!(
+            "R*c1".parse(),
+            Ok(Move::Drop {
+                piece: HandPiece::Rook,
+                to: 2
+            })
+        );
+    }
+
+    #[test]
+    fn test_roundtrip() {
+        for mv in Position::startpos().legal_moves() {
+            assert_eq!(mv.to_string().parse(), Ok(mv));
+        }
+        let position = parse_sfen("1k1/3/3/1K1 b PBRpbr").unwrap();
+        for mv in position.legal_moves() {
+            assert_eq!(mv.to_string().parse(), Ok(mv));
+        }
+    }
+
+    #[test]
+    fn test_syntax_errors() {
+        let cases = [
+            ("", 0, "a field from a1 to c4"),
+            ("b3", 2, "a field from a1 to c4"),
+            ("d3b2", 0, "a field from a1 to c4"),
+            ("b3b5", 2, "a field from a1 to c4"),
+            ("K*b2", 0, "one of 'P', 'B' or 'R' to drop"),
+            ("p*b2", 0, "one of 'P', 'B' or 'R' to drop"),
+            ("P*b2+", 4, "end of the move"),
+            ("b3b2x", 4, "end of the move"),
+        ];
+
+        for (text, offset, expected) in cases {
+            assert_eq!(
+                text.parse::<Move>(),
+                Err(MoveError::Syntax { offset, expected }),
+                "{}",
+                text
+            );
+        }
+    }
+
+    #[test]
+    fn test_parse_legal_move() {
+        let position = Position::startpos();
+
+        assert_eq!(
+            position.parse_move("b3b2"),
+            Ok(Move::Board { from: 7, to: 4 })
+        );
+        assert_eq!(
+            position.parse_move("b3b1"),
+            Err(MoveError::Illegal {
+                mv: Move::Board { from: 7, to: 1 }
+            })
+        );
+        assert_eq!(
+            position.parse_move("P*a3"),
+            Err(MoveError::Illegal {
+                mv: Move::Drop {
+                    piece: HandPiece::Pawn,
+                    to: 6
+                }
+            })
+        );
+    }
+
+    #[test]
+    fn test_promotion_mark() {
+        let position = parse_sfen("1k1/P2/3/1K1 b pb2r2").unwrap();
+        let promotion = Move::Board { from: 3, to: 0 };
+
+        assert!(position.is_promotion(promotion));
+        assert_eq!(position.move_notation(promotion), "a2a1+");
+        assert_eq!(position.parse_move("a2a1+"), Ok(promotion));
+        assert_eq!(position.parse_move("a2a1"), Ok(promotion));
+        assert_eq!(
+            position.parse_move("b4a3+"),
+            Err(MoveError::Promotion {
+                mv: Move::Board { from: 10, to: 6 }
+            })
+        );
+    }
+
+    #[test]
+    fn test_error_display() {
+        let error = "b3".parse::<Move>().unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "invalid move at byte 2: expected a field from a1 to c4"
+        );
+        let error = Position::startpos().parse_move("b3b1").unwrap_err();
+        assert_eq!(error.to_string(), "illegal move b3b1");
+    }
+}
@@ -1,6 +1,111 @@
-use std::collections::HashMap;
+use std::fmt;
+use std::str::FromStr;
 
 use crate::game::*;
+use crate::sfen::Sfen;
+
+/// Reason why a SFEN string could not be parsed.
+///
+/// Offsets are byte offsets into the whole SFEN string.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SfenError {
+    Board {
+        offset: usize,
+        expected: &'static str,
+    },
+    Side {
+        offset: usize,
+        expected: &'static str,
+    },
+    Hands {
+        offset: usize,
+        expected: &'static str,
+    },
+    MoveNumber {
+        offset: usize,
+        expected: &'static str,
+    },
+    TrailingInput {
+        offset: usize,
+    },
+    /// The SFEN is well formed, but describes a position
+    /// which can't occur in a game.
+    IllegalPosition {
+        reason: &'static str,
+    },
+}
+
+impl SfenError {
+    pub fn offset(&self) -> Option<usize> {
+        match *self {
+            SfenError::Board { offset, .. }
+            | SfenError::Side { offset, .. }
+            | SfenError::Hands { offset, .. }
+            | SfenError::MoveNumber { offset, .. }
+            | SfenError::TrailingInput { offset } => Some(offset),
+            SfenError::IllegalPosition { .. } => None,
+        }
+    }
+
+    /// The same error, with the offset moved by `base` bytes.
+    fn shifted(self, base: usize) -> Self {
+        match self {
+            SfenError::Board { offset, expected } => SfenError::Board {
+                offset: offset + base,
+                expected,
+            },
+            SfenError::Side { offset, expected } => SfenError::Side {
+                offset: offset + base,
+                expected,
+            },
+            SfenError::Hands { offset, expected } => SfenError::Hands {
+                offset: offset + base,
+                expected,
+            },
+            SfenError::MoveNumber { offset, expected } => SfenError::MoveNumber {
+                offset: offset + base,
+                expected,
+            },
+            SfenError::TrailingInput { offset } => SfenError::TrailingInput {
+                offset: offset + base,
+            },
+            SfenError::IllegalPosition { reason } => SfenError::IllegalPosition { reason },
+        }
+    }
+}
+
+impl fmt::Display for SfenError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SfenError::Board { offset, expected } => {
+                write!(f, "invalid board at byte {}: expected {}", offset, expected)
+            }
+            SfenError::Side { offset, expected } => {
+                write!(
+                    f,
+                    "invalid side to play at byte {}: expected {}",
+                    offset, expected
+                )
+            }
+            SfenError::Hands { offset, expected } => {
+                write!(f, "invalid hands at byte {}: expected {}", offset, expected)
+            }
+            SfenError::MoveNumber { offset, expected } => {
+                write!(
+                    f,
+                    "invalid move number at byte {}: expected {}",
+                    offset, expected
+                )
+            }
+            SfenError::TrailingInput { offset } => {
+                write!(f, "unexpected input at byte {}", offset)
+            }
+            SfenError::IllegalPosition { reason } => write!(f, "illegal position: {}", reason),
+        }
+    }
+}
+
+impl std::error::Error for SfenError {}
 
 fn parse_color(color: &str) -> Option<Color> {
     match color {
@@ -32,50 +137,92 @@ enum RowLexerOutput {
     Piece(Piece),
 }
 
-fn lex_row(row: &str) -> Vec<RowLexerOutput> {
+/// Splits a row into digits and pieces, checking that it describes
+/// exactly `COLS` fields.
+fn lex_row(row: &str) -> Result<Vec<RowLexerOutput>, SfenError> {
     let mut result = vec![];
-    let chars: Vec<char> = row.chars().collect();
+    let chars: Vec<(usize, char)> = row.char_indices().collect();
+    let mut fields = 0;
 
     let mut i = 0;
     while i < chars.len() {
-        let ch = chars[i];
-
-        if ch == '+' {
-            i += 1;
-            continue;
-        }
-
-        if let Some(digit) = ch.to_digit(10) {
-            result.push(RowLexerOutput::Digit(digit as usize));
+        let (offset, ch) = chars[i];
+
+        let (token, width) = if let Some(digit) = ch.to_digit(10) {
+            if digit == 0 {
+                return Err(SfenError::Board {
+                    offset,
+                    expected: "a number of empty fields from 1 to 3",
+                });
+            }
             i += 1;
+            (RowLexerOutput::Digit(digit as usize), digit as usize)
+        } else if ch == '+' {
+            return Err(SfenError::Board {
+                offset,
+                expected: "'+' only after a chick",
+            });
         } else {
-            let piece = if i + 1 < chars.len() && chars[i + 1] == '+' {
-                String::from(ch) + &String::from(chars[i + 1])
-            } else {
-                String::from(ch)
+            let single = String::from(ch);
+            let piece = parse_piece(&single).ok_or(SfenError::Board {
+                offset,
+                expected: "a piece letter or a digit",
+            })?;
+
+            let piece = match chars.get(i + 1) {
+                Some(&(plus_offset, '+')) => {
+                    i += 1;
+                    parse_piece(&(single + "+")).ok_or(SfenError::Board {
+                        offset: plus_offset,
+                        expected: "'+' only after a chick",
+                    })?
+                }
+                _ => piece,
             };
-
-            if let Some(piece) = parse_piece(&piece) {
-                result.push(RowLexerOutput::Piece(piece));
-            }
-            i += 1
+            i += 1;
+            (RowLexerOutput::Piece(piece), 1)
+        };
+
+        fields += width;
+        if fields > COLS {
+            return Err(SfenError::Board {
+                offset,
+                expected: "at most 3 fields in a row",
+            });
         }
+        result.push(token);
+    }
+
+    if fields < COLS {
+        return Err(SfenError::Board {
+            offset: row.len(),
+            expected: "exactly 3 fields in a row",
+        });
     }
 
-    result
+    Ok(result)
 }
 
-fn parse_board(board: &str) -> Option<Board> {
-    let rows: Vec<&str> = board.split("/").collect();
-    if rows.len() != 4 {
-        return None;
+fn parse_board(board: &str) -> Result<Board, SfenError> {
+    let rows: Vec<&str> = board.split('/').collect();
+    if rows.len() != ROWS {
+        return Err(SfenError::Board {
+            offset: rows
+                .iter()
+                .take(ROWS)
+                .map(|row| row.len() + 1)
+                .sum::<usize>()
+                - 1,
+            expected: "4 rows separated by '/'",
+        });
     }
 
     let mut board = Board::empty();
     let mut current_field = 0;
+    let mut row_offset = 0;
 
     for row in rows {
-        for token in lex_row(row) {
+        for token in lex_row(row).map_err(|e| e.shifted(row_offset))? {
             match token {
                 RowLexerOutput::Digit(digit) => {
                     current_field += digit;
@@ -86,9 +233,10 @@ fn parse_board(board: &str) -> Option<Board> {
                 }
             }
         }
+        row_offset += row.len() + 1;
     }
 
-    Some(board)
+    Ok(board)
 }
 
 fn parse_hand_piece(piece: &str) -> Option<HandPiece> {
@@ -107,34 +255,56 @@ struct HandLexerOutput {
     count: usize,
 }
 
-fn lex_hand(hands: &str) -> Option<Vec<HandLexerOutput>> {
-    let mut result = vec![];
+fn lex_hand(hands: &str) -> Result<Vec<HandLexerOutput>, SfenError> {
+    let mut result: Vec<HandLexerOutput> = vec![];
 
     if hands == "-" {
-        return Some(result);
+        return Ok(result);
     }
 
-    let chars: Vec<char> = hands.chars().collect();
+    let chars: Vec<(usize, char)> = hands.char_indices().collect();
 
     let mut i = 0;
     while i < chars.len() {
-        if chars[i].is_digit(10) {
-            i += 1;
-            continue;
-        }
+        let (offset, ch) = chars[i];
         let mut color = Color::White;
-        if chars[i].is_uppercase() {
+        if ch.is_uppercase() {
             color = Color::Black;
         }
-        let string = String::from(chars[i]).to_ascii_lowercase();
-        let piece = parse_hand_piece(&string)?;
+        let string = String::from(ch).to_ascii_lowercase();
+        let piece = parse_hand_piece(&string).ok_or(SfenError::Hands {
+            offset,
+            expected: "one of 'p', 'b', 'r', 'P', 'B', 'R' or '-'",
+        })?;
+
+        if result
+            .iter()
+            .any(|token| token.piece == piece && token.color == color)
+        {
+            return Err(SfenError::Hands {
+                offset,
+                expected: "each piece listed at most once",
+            });
+        }
+        i += 1;
 
         let mut count = 1;
 
-        if i + 1 < chars.len() {
-            if let Some(digit) = chars[i + 1].to_digit(10) {
-                count = digit as usize;
-            }
+        let digits_start = i;
+        while i < chars.len() && chars[i].1.is_ascii_digit() {
+            i += 1;
+        }
+        if i > digits_start {
+            let (digits_offset, _) = chars[digits_start];
+            let digits_end = chars.get(i).map_or(hands.len(), |&(end, _)| end);
+            count = hands[digits_offset..digits_end]
+                .parse()
+                .ok()
+                .filter(|count| (1..=MAX_HAND_COUNT).contains(count))
+                .ok_or(SfenError::Hands {
+                    offset: digits_offset,
+                    expected: "a count of 1 or 2",
+                })?;
         }
 
         result.push(HandLexerOutput {
@@ -142,10 +312,9 @@ fn lex_hand(hands: &str) -> Option<Vec<HandLexerOutput>> {
             color,
             count,
         });
-        i += 1;
     }
 
-    Some(result)
+    Ok(result)
 }
 
 struct Hands {
@@ -153,9 +322,9 @@ struct Hands {
     black_hand: Hand,
 }
 
-fn parse_hands(hands: &str) -> Option<Hands> {
-    let mut white_hand = Hand(HashMap::new());
-    let mut black_hand = Hand(HashMap::new());
+fn parse_hands(hands: &str) -> Result<Hands, SfenError> {
+    let mut white_hand = Hand::empty();
+    let mut black_hand = Hand::empty();
 
     for token in lex_hand(hands)? {
         let HandLexerOutput {
@@ -166,41 +335,216 @@ fn parse_hands(hands: &str) -> Option<Hands> {
 
         match color {
             Color::White => {
-                white_hand.0.insert(piece, count);
+                white_hand.set(piece, count);
             }
             Color::Black => {
-                black_hand.0.insert(piece, count);
+                black_hand.set(piece, count);
             }
         }
     }
 
-    Some(Hands {
+    Ok(Hands {
         white_hand,
         black_hand,
     })
 }
 
-fn parse_sfen(sfen: &str) -> Option<Position> {
-    let parts: Vec<&str> = sfen.split_ascii_whitespace().collect();
-    if parts.len() != 3 {
+/// How strictly the SFEN should be checked.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub enum SfenMode {
+    /// Hand pieces may come in any order and counts of 1 may be written out.
+    #[default]
+    Lenient,
+    /// Hands must be written exactly as [`Sfen`] renders them: Black's pieces
+    /// before White's, chicks before elephants before giraffes, and no count
+    /// for a single piece.
+    Canonical,
+}
+
+/// Checks that the hands are written the same way as they are rendered.
+fn check_canonical_hands(hands: &str, parsed: &Hands) -> Result<(), SfenError> {
+    let mut canonical = parsed.black_hand.sfen().to_ascii_uppercase() + &parsed.white_hand.sfen();
+    if canonical.is_empty() {
+        canonical = "-".to_owned();
+    }
+
+    if hands == canonical {
+        return Ok(());
+    }
+
+    let offset = hands
+        .bytes()
+        .zip(canonical.bytes())
+        .position(|(given, expected)| given != expected)
+        .unwrap_or(hands.len().min(canonical.len()));
+    Err(SfenError::Hands {
+        offset,
+        expected: "hands in canonical order, e.g. 'P2Bbr'",
+    })
+}
+
+fn parse_move_number(move_number: &str) -> Option<u32> {
+    if !move_number.chars().all(|ch| ch.is_ascii_digit()) {
         return None;
     }
+    move_number.parse().ok().filter(|&n| n > 0)
+}
 
-    let board = parse_board(parts[0])?;
+/// Splits the SFEN into whitespace separated fields,
+/// keeping the byte offset at which each of them starts.
+fn split_fields(sfen: &str) -> Vec<(usize, &str)> {
+    let mut fields = vec![];
+    let mut start = None;
+
+    for (offset, ch) in sfen.char_indices() {
+        match (ch.is_ascii_whitespace(), start) {
+            (true, Some(begin)) => {
+                fields.push((begin, &sfen[begin..offset]));
+                start = None;
+            }
+            (false, None) => start = Some(offset),
+            _ => {}
+        }
+    }
+    if let Some(begin) = start {
+        fields.push((begin, &sfen[begin..]));
+    }
 
-    let to_play = parse_color(parts[1])?;
+    fields
+}
 
+pub(crate) fn parse_sfen(sfen: &str) -> Result<Position, SfenError> {
+    parse_sfen_with(sfen, SfenMode::Lenient)
+}
+
+pub(crate) fn parse_sfen_with(sfen: &str, mode: SfenMode) -> Result<Position, SfenError> {
+    let fields = split_fields(sfen);
+    let end = sfen.len();
+
+    let &(board_offset, board) = fields.first().ok_or(SfenError::Board {
+        offset: end,
+        expected: "a board",
+    })?;
+    let board = parse_board(board).map_err(|e| e.shifted(board_offset))?;
+
+    let &(side_offset, side) = fields.get(1).ok_or(SfenError::Side {
+        offset: end,
+        expected: "'b' or 'w'",
+    })?;
+    let to_play = parse_color(side).ok_or(SfenError::Side {
+        offset: side_offset,
+        expected: "'b' or 'w'",
+    })?;
+
+    let &(hands_offset, hands) = fields.get(2).ok_or(SfenError::Hands {
+        offset: end,
+        expected: "hands or '-'",
+    })?;
+    let parsed_hands = parse_hands(hands).map_err(|e| e.shifted(hands_offset))?;
+    if mode == SfenMode::Canonical {
+        check_canonical_hands(hands, &parsed_hands).map_err(|e| e.shifted(hands_offset))?;
+    }
     let Hands {
         white_hand,
         black_hand,
-    } = parse_hands(parts[2])?;
+    } = parsed_hands;
+
+    let move_number = match fields.get(3) {
+        Some(&(offset, move_number)) => {
+            Some(parse_move_number(move_number).ok_or(SfenError::MoveNumber {
+                offset,
+                expected: "a positive number",
+            })?)
+        }
+        None => None,
+    };
 
-    Some(Position {
-        board,
-        to_play,
-        black_hand,
-        white_hand,
-    })
+    if let Some(&(offset, _)) = fields.get(4) {
+        return Err(SfenError::TrailingInput { offset });
+    }
+
+    let mut position = checked_position(board, to_play, black_hand, white_hand)?;
+    position.set_move_number(move_number);
+
+    Ok(position)
+}
+
+/// Creates the position after checking that it can occur in a game.
+pub(crate) fn checked_position(
+    board: Board,
+    to_play: Color,
+    black_hand: Hand,
+    white_hand: Hand,
+) -> Result<Position, SfenError> {
+    validate_material(&board, &black_hand, &white_hand)?;
+
+    Ok(Position::new(board, to_play, black_hand, white_hand))
+}
+
+impl Position {
+    pub fn from_sfen(sfen: &str, mode: SfenMode) -> Result<Self, SfenError> {
+        parse_sfen_with(sfen, mode)
+    }
+}
+
+impl FromStr for BitBoard {
+    type Err = SfenError;
+
+    /// Parses the board field of a SFEN.
+    fn from_str(board: &str) -> Result<Self, Self::Err> {
+        parse_board(board).map(|board| BitBoard::from(&board))
+    }
+}
+
+impl FromStr for Position {
+    type Err = SfenError;
+
+    fn from_str(sfen: &str) -> Result<Self, Self::Err> {
+        parse_sfen(sfen)
+    }
+}
+
+/// Checks that the board and the hands hold the material a game is played
+/// with: two chicks (hens included), two elephants and two giraffes, and at
+/// most one lion of each color, as a lion can already have been captured.
+fn validate_material(board: &Board, black_hand: &Hand, white_hand: &Hand) -> Result<(), SfenError> {
+    let mut lions = [0; 2];
+    let mut material = [0; 3];
+
+    for field in ALL_INDEXES {
+        if let Some(piece) = board[field] {
+            match HandPiece::try_from(piece.kind) {
+                Ok(hand_piece) => material[hand_piece as usize] += 1,
+                Err(()) => lions[piece.color as usize] += 1,
+            }
+        }
+    }
+    for hand in [black_hand, white_hand] {
+        for (piece, count) in hand.iter() {
+            material[piece as usize] += count;
+        }
+    }
+
+    if lions.iter().any(|&count| count > 1) {
+        return Err(SfenError::IllegalPosition {
+            reason: "more than one lion of the same color",
+        });
+    }
+
+    let reasons = [
+        "not exactly two chicks",
+        "not exactly two elephants",
+        "not exactly two giraffes",
+    ];
+    for piece in ALL_HAND_PIECES {
+        if material[piece as usize] != MAX_HAND_COUNT {
+            return Err(SfenError::IllegalPosition {
+                reason: reasons[piece as usize],
+            });
+        }
+    }
+
+    Ok(())
 }
 
 #[cfg(test)]
@@ -261,7 +605,7 @@ mod tests {
 
         let result_board = parse_board(input);
 
-        assert!(result_board.is_none());
+        assert!(result_board.is_err());
     }
 
     #[test]
@@ -342,7 +686,7 @@ mod tests {
 
         for (string, expected_vec) in inputs {
             let result = lex_row(string);
-            assert_eq!(result, expected_vec);
+            assert_eq!(result, Ok(expected_vec));
         }
     }
 
@@ -415,7 +759,7 @@ mod tests {
 
         for (string, expected) in inputs {
             let result = lex_hand(string);
-            assert_eq!(result, Some(expected));
+            assert_eq!(result, Ok(expected));
         }
     }
 
@@ -447,7 +791,7 @@ mod tests {
 
         for (string, expected) in inputs {
             let result = lex_hand(string);
-            assert_eq!(result, Some(expected));
+            assert_eq!(result, Ok(expected));
         }
     }
 
@@ -460,8 +804,8 @@ mod tests {
             black_hand,
         } = parse_hands(input).unwrap();
 
-        assert_eq!(white_hand, Hand(HashMap::new()));
-        assert_eq!(black_hand, Hand(HashMap::new()));
+        assert_eq!(white_hand, Hand::empty());
+        assert_eq!(black_hand, Hand::empty());
     }
 
     #[test]
@@ -473,13 +817,13 @@ mod tests {
             black_hand,
         } = parse_hands(input).unwrap();
 
-        assert_eq!(white_hand.0.get(&HandPiece::Pawn), Some(&1));
-        assert_eq!(white_hand.0.get(&HandPiece::Bishop), None);
-        assert_eq!(white_hand.0.get(&HandPiece::Rook), Some(&2));
+        assert_eq!(white_hand.count(HandPiece::Pawn), 1);
+        assert_eq!(white_hand.count(HandPiece::Bishop), 0);
+        assert_eq!(white_hand.count(HandPiece::Rook), 2);
 
-        assert_eq!(black_hand.0.get(&HandPiece::Pawn), Some(&1));
-        assert_eq!(black_hand.0.get(&HandPiece::Bishop), Some(&2));
-        assert_eq!(black_hand.0.get(&HandPiece::Rook), None);
+        assert_eq!(black_hand.count(HandPiece::Pawn), 1);
+        assert_eq!(black_hand.count(HandPiece::Bishop), 2);
+        assert_eq!(black_hand.count(HandPiece::Rook), 0);
     }
 
     #[test]
@@ -492,7 +836,7 @@ mod tests {
 
         for input in wrong_inputs {
             let result = parse_sfen(input);
-            assert!(result.is_none());
+            assert!(result.is_err());
         }
     }
 
@@ -502,6 +846,310 @@ mod tests {
 
         let result = parse_sfen(input);
 
-        assert!(result.is_some());
+        assert!(result.is_ok());
+    }
+
+    #[test]
+    fn test_parse_sfen_error_locations() {
+        let inputs = [
+            (
+                "",
+                SfenError::Board {
+                    offset: 0,
+                    expected: "a board",
+                },
+            ),
+            (
+                "3/3/3 b -",
+                SfenError::Board {
+                    offset: 5,
+                    expected: "4 rows separated by '/'",
+                },
+            ),
+            (
+                "3/3/3/3",
+                SfenError::Side {
+                    offset: 7,
+                    expected: "'b' or 'w'",
+                },
+            ),
+            (
+                "3/3/3/3 z -",
+                SfenError::Side {
+                    offset: 8,
+                    expected: "'b' or 'w'",
+                },
+            ),
+            (
+                "3/3/3/3 b",
+                SfenError::Hands {
+                    offset: 9,
+                    expected: "hands or '-'",
+                },
+            ),
+            (
+                "3/3/3/3  w   Px",
+                SfenError::Hands {
+                    offset: 14,
+                    expected: "one of 'p', 'b', 'r', 'P', 'B', 'R' or '-'",
+                },
+            ),
+            (
+                "3/3/3/3/3 b -",
+                SfenError::Board {
+                    offset: 7,
+                    expected: "4 rows separated by '/'",
+                },
+            ),
+            (
+                "3/3/3/3 b - x",
+                SfenError::MoveNumber {
+                    offset: 12,
+                    expected: "a positive number",
+                },
+            ),
+            (
+                "3/3/3/3 b - 0",
+                SfenError::MoveNumber {
+                    offset: 12,
+                    expected: "a positive number",
+                },
+            ),
+            (
+                "3/3/3/3 b - +1",
+                SfenError::MoveNumber {
+                    offset: 12,
+                    expected: "a positive number",
+                },
+            ),
+            ("3/3/3/3 b - 1 x", SfenError::TrailingInput { offset: 14 }),
+        ];
+
+        for (input, expected_error) in inputs {
+            let result = parse_sfen(input);
+            assert_eq!(result, Err(expected_error), "{}", input);
+        }
+    }
+
+    #[test]
+    fn test_sfen_error_display() {
+        let error = parse_sfen("3/3/3/3 z -").unwrap_err();
+
+        assert_eq!(
+            error.to_string(),
+            "invalid side to play at byte 8: expected 'b' or 'w'"
+        );
+    }
+
+    #[test]
+    fn test_row_lexer_errors() {
+        let inputs = [
+            ("4", 0, "at most 3 fields in a row"),
+            ("k9", 1, "at most 3 fields in a row"),
+            ("pppp", 3, "at most 3 fields in a row"),
+            ("1p2", 2, "at most 3 fields in a row"),
+            ("2", 1, "exactly 3 fields in a row"),
+            ("", 0, "exactly 3 fields in a row"),
+            ("0p2", 0, "a number of empty fields from 1 to 3"),
+            ("kxp", 1, "a piece letter or a digit"),
+            ("k+2", 1, "'+' only after a chick"),
+            ("+p2", 0, "'+' only after a chick"),
+            ("p++1", 2, "'+' only after a chick"),
+        ];
+
+        for (string, offset, expected) in inputs {
+            let result = lex_row(string);
+            assert_eq!(
+                result,
+                Err(SfenError::Board { offset, expected }),
+                "{}",
+                string
+            );
+        }
+    }
+
+    #[test]
+    fn test_parse_board_error_offsets() {
+        let inputs = [
+            ("4/3/3/3", 0),
+            ("3/3/3/k9", 7),
+            ("3/3/k?1/3", 5),
+            ("3/1p/3/3", 4),
+        ];
+
+        for (board, offset) in inputs {
+            let result = parse_board(board).unwrap_err();
+            assert_eq!(result.offset(), Some(offset), "{}", board);
+        }
+    }
+
+    #[test]
+    fn test_parse_illegal_positions() {
+        let inputs = [
+            (
+                "kk1/3/3/1K1 b P2B2R2",
+                "more than one lion of the same color",
+            ),
+            (
+                "1k1/3/3/KK1 w p2b2r2",
+                "more than one lion of the same color",
+            ),
+            ("1k1/1p1/1P1/1K1 b PB2R2", "not exactly two chicks"),
+            ("1k1/1p+1/1P1/1K1 b pB2R2", "not exactly two chicks"),
+            ("1k1/3/1P1/1K1 b B2R2", "not exactly two chicks"),
+            ("1k1/3/3/1K1 b -", "not exactly two chicks"),
+            ("bk1/3/3/1KB w P2R2b", "not exactly two elephants"),
+            ("1k1/3/3/1K1 b P2BR2", "not exactly two elephants"),
+            ("1k1/3/3/1K1 b P2B2R2r", "not exactly two giraffes"),
+        ];
+
+        for (sfen, reason) in inputs {
+            let result = parse_sfen(sfen);
+            assert_eq!(
+                result,
+                Err(SfenError::IllegalPosition { reason }),
+                "{}",
+                sfen
+            );
+        }
+    }
+
+    #[test]
+    fn test_parse_legal_positions() {
+        let inputs = [
+            "rkb/1p1/1P1/BKR b -",
+            "rkb/1P1/3/BKR w P 2",
+            "1k1/1R1/3/1K1 b P2B2r",
+            "1k1/1R1/3/1K1 w P2B2r",
+            "1k1/3/1r1/1K1 b P2B2R",
+            "k2/3/1p1/1K1 w pB2R2",
+            "k2/3/1p1/1K1 b PB2R2",
+            "1k1/3/3/1K1 b P2B2r2",
+            "1k1/1p+1/1P+1/1K1 w B2R2",
+            "1R1/3/3/1K1 w P2B2r",
+            "3/3/3/3 b P2B2R2",
+        ];
+
+        for sfen in inputs {
+            assert!(parse_sfen(sfen).is_ok(), "{}", sfen);
+        }
+    }
+
+    #[test]
+    fn test_position_from_str() {
+        let position: Position = "rkb/1p1/1P1/BKR b -".parse().unwrap();
+
+        assert_eq!(position, Position::startpos());
+        assert!("rkb/1p1/1P1/BKR x -".parse::<Position>().is_err());
+    }
+
+    #[test]
+    fn test_parse_move_number() {
+        let position = parse_sfen("rkb/1p1/1P1/BKR w - 2").unwrap();
+        assert_eq!(position.move_number(), Some(2));
+
+        let position = parse_sfen("rkb/1p1/1P1/BKR w -").unwrap();
+        assert_eq!(position.move_number(), None);
+    }
+
+    #[test]
+    fn test_hand_lexer_multi_digit_counts() {
+        let result = lex_hand("P02b2");
+
+        assert_eq!(
+            result,
+            Ok(vec![
+                HandLexerOutput {
+                    piece: HandPiece::Pawn,
+                    color: Color::Black,
+                    count: 2,
+                },
+                HandLexerOutput {
+                    piece: HandPiece::Bishop,
+                    color: Color::White,
+                    count: 2,
+                },
+            ])
+        );
+    }
+
+    #[test]
+    fn test_hand_lexer_errors() {
+        let inputs = [
+            ("pp", 1, "each piece listed at most once"),
+            ("P2bP", 3, "each piece listed at most once"),
+            ("p0", 1, "a count of 1 or 2"),
+            ("r3", 1, "a count of 1 or 2"),
+            ("Pb12", 2, "a count of 1 or 2"),
+            ("2p", 0, "one of 'p', 'b', 'r', 'P', 'B', 'R' or '-'"),
+            ("p-", 1, "one of 'p', 'b', 'r', 'P', 'B', 'R' or '-'"),
+        ];
+
+        for (string, offset, expected) in inputs {
+            let result = lex_hand(string);
+            assert_eq!(
+                result,
+                Err(SfenError::Hands { offset, expected }),
+                "{}",
+                string
+            );
+        }
+    }
+
+    #[test]
+    fn test_parse_hands_ignores_order() {
+        let a = parse_sfen("1k1/3/3/1K1 b rP2bBR").unwrap();
+        let b = parse_sfen("1k1/3/3/1K1 b P2BRbr").unwrap();
+
+        assert_eq!(a, b);
+    }
+
+    #[test]
+    fn test_canonical_mode() {
+        let canonical = [
+            "1k1/3/3/1K1 b P2B2R2",
+            "1k1/3/3/1K1 b P2Bbr2",
+            "1k1/3/3/1K1 w p2b2r2",
+        ];
+        for sfen in canonical {
+            assert!(
+                Position::from_sfen(sfen, SfenMode::Canonical).is_ok(),
+                "{}",
+                sfen
+            );
+        }
+
+        let non_canonical = [
+            ("1k1/3/3/1K1 b bP2Br2", 14),
+            ("1k1/3/3/pK1 b P1B2R2", 15),
+            ("1k1/3/3/1K1 b P02B2R2", 15),
+            ("1k1/3/3/1K1 b pPB2R2", 14),
+            ("1k1/3/3/1K1 b P2BRrb", 18),
+        ];
+        for (sfen, offset) in non_canonical {
+            let result = Position::from_sfen(sfen, SfenMode::Canonical);
+            assert_eq!(
+                result,
+                Err(SfenError::Hands {
+                    offset,
+                    expected: "hands in canonical order, e.g. 'P2Bbr'",
+                }),
+                "{}",
+                sfen
+            );
+            assert!(
+                Position::from_sfen(sfen, SfenMode::Lenient).is_ok(),
+                "{}",
+                sfen
+            );
+        }
+    }
+
+    #[test]
+    fn test_bitboard_from_str() {
+        let bitboard: BitBoard = "rkb/1p1/1P1/BKR".parse().unwrap();
+
+        assert_eq!(bitboard, BitBoard::from(Position::startpos().board()));
+        assert!("rkb/1p1/1P1".parse::<BitBoard>().is_err());
     }
 }
@@ -0,0 +1,181 @@
+use crate::game::*;
+
+/// Number of leaf nodes of the game tree of the given depth.
+///
+/// Positions in which the game has ended (a lion was captured or a try
+/// succeeded) have no moves, so they are counted only at full depth.
+pub fn perft(position: &mut Position, depth: usize) -> u64 {
+    if depth == 0 {
+        return 1;
+    }
+    if position.result() != GameResult::Ongoing {
+        return 0;
+    }
+
+    let moves = position.legal_moves();
+    if depth == 1 {
+        return moves.len() as u64;
+    }
+
+    let mut nodes = 0;
+    for mv in moves {
+        let undo = position.make_move(mv);
+        nodes += perft(position, depth - 1);
+        position.unmake_move(mv, undo);
+    }
+    nodes
+}
+
+/// Perft split by the first move, useful for finding
+/// where two move generators disagree.
+pub fn divide(position: &mut Position, depth: usize) -> Vec<(Move, u64)> {
+    if depth == 0 || position.result() != GameResult::Ongoing {
+        return vec![];
+    }
+
+    position
+        .legal_moves()
+        .into_iter()
+        .map(|mv| {
+            let undo = position.make_move(mv);
+            let nodes = perft(position, depth - 1);
+            position.unmake_move(mv, undo);
+            (mv, nodes)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse_sfen;
+
+    /// Straightforward perft which copies positions and uses the step
+    /// patterns directly, to check the bitboard move generator against.
+    fn naive_perft(position: Position, depth: usize) -> u64 {
+        if depth == 0 {
+            return 1;
+        }
+        if position.result() != GameResult::Ongoing {
+            return 0;
+        }
+
+        let mut moves = vec![];
+        for from in ALL_INDEXES {
+            let Some(piece) = position.board()[from] else {
+                continue;
+            };
+            if piece.color != position.to_play() {
+                continue;
+            }
+            for step in piece.steps() {
+                if let Some(to) = step_field(from, step) {
+                    if position.board()[to].map(|target| target.color) != Some(piece.color) {
+                        moves.push(Move::Board { from, to });
+                    }
+                }
+            }
+        }
+        for piece in ALL_HAND_PIECES {
+            if position.hand(position.to_play()).count(piece) > 0 {
+                for to in ALL_INDEXES {
+                    if position.board()[to].is_none() {
+                        moves.push(Move::Drop { piece, to });
+                    }
+                }
+            }
+        }
+
+        moves
+            .into_iter()
+            .map(|mv| {
+                let mut child = position;
+                child.make_move(mv);
+                naive_perft(child, depth - 1)
+            })
+            .sum()
+    }
+
+    // The counts below agree at every depth up to 5 with the reference
+    // generator of `tests/perft_reference.rs`, which implements the rules
+    // again without any code of the crate. There is no published table of
+    // Dobutsu Shogi perft counts to compare them with.
+    const STARTPOS_PERFT: [u64; 7] = [1, 4, 17, 123, 976, 8122, 71677];
+
+    #[test]
+    fn test_startpos_perft() {
+        let mut position = Position::startpos();
+
+        for (depth, &expected) in STARTPOS_PERFT.iter().enumerate() {
+            assert_eq!(perft(&mut position, depth), expected, "depth {}", depth);
+        }
+        assert_eq!(position, Position::startpos());
+    }
+
+    #[test]
+    fn test_tricky_positions_perft() {
+        // Positions exercising drops, promotions, captured hens and tries,
+        // with counts checked like `STARTPOS_PERFT`.
+        let inputs: [(&str, &[u64]); 5] = [
+            ("r1b/1k1/3/BPK b Pr", &[1, 10, 134, 879, 9006, 57522]),
+            (
+                "1k1/p2/3/1K1 w PBRbr",
+                &[1, 23, 682, 10544, 218878, 2631020],
+            ),
+            ("k2/1P1/3/2K b BRbpr", &[1, 22, 606, 8807, 168118, 1957993]),
+            ("2k/1p+1/R2/K1b w PBr", &[1, 16, 275, 3457, 41893, 504552]),
+            ("2k/K2/1p1/2B b Pbr2", &[1, 14, 246, 2338, 34920, 379505]),
+        ];
+
+        for (sfen, counts) in inputs {
+            let mut position = parse_sfen(sfen).unwrap();
+            for (depth, &expected) in counts.iter().enumerate() {
+                assert_eq!(
+                    perft(&mut position, depth),
+                    expected,
+                    "{} depth {}",
+                    sfen,
+                    depth
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_perft_matches_naive_generator() {
+        let sfens = [
+            "rkb/1p1/1P1/BKR b -",
+            "r1b/1k1/3/BPK b Pr",
+            "1k1/p2/3/1K1 w PBRbr",
+            "k2/1P1/3/2K b BRbpr",
+            "2k/1p+1/R2/K1b w PBr",
+            "2k/K2/1p1/2B b Pbr2",
+        ];
+
+        for sfen in sfens {
+            let mut position = parse_sfen(sfen).unwrap();
+            for depth in 0..4 {
+                assert_eq!(
+                    perft(&mut position, depth),
+                    naive_perft(position, depth),
+                    "{} depth {}",
+                    sfen,
+                    depth
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_divide_sums_to_perft() {
+        let mut position = Position::startpos();
+
+        let result = divide(&mut position, 4);
+
+        assert_eq!(result.len(), 4);
+        assert_eq!(
+            result.iter().map(|&(_, nodes)| nodes).sum::<u64>(),
+            perft(&mut position, 4)
+        );
+    }
+}
@@ -0,0 +1,748 @@
+use std::fmt;
+use std::str::FromStr;
+
+use crate::game::*;
+use crate::notation::MoveError;
+use crate::parser::{parse_sfen, SfenError};
+use crate::search::Score;
+use crate::sfen::Sfen;
+
+const SFEN_HEADER: &str = "Sfen";
+const RESULT_HEADER: &str = "Result";
+/// Movetext lines are wrapped after this many characters.
+const LINE_WIDTH: usize = 79;
+
+fn result_name(result: GameResult) -> &'static str {
+    match result {
+        GameResult::Ongoing => "*",
+        GameResult::BlackWin => "1-0",
+        GameResult::WhiteWin => "0-1",
+        GameResult::Draw => "1/2-1/2",
+    }
+}
+
+fn parse_result(name: &str) -> Option<GameResult> {
+    [
+        GameResult::Ongoing,
+        GameResult::BlackWin,
+        GameResult::WhiteWin,
+        GameResult::Draw,
+    ]
+    .into_iter()
+    .find(|&result| result_name(result) == name)
+}
+
+/// Reason why a game record could not be read. Lines are numbered from 1.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RecordError {
+    Syntax { line: usize, expected: &'static str },
+    Sfen { line: usize, error: SfenError },
+    Move { line: usize, error: MoveError },
+}
+
+impl fmt::Display for RecordError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RecordError::Syntax { line, expected } => {
+                write!(f, "invalid record at line {}: expected {}", line, expected)
+            }
+            RecordError::Sfen { line, error } => {
+                write!(f, "invalid starting position at line {}: {}", line, error)
+            }
+            RecordError::Move { line, error } => write!(f, "line {}: {}", line, error),
+        }
+    }
+}
+
+impl std::error::Error for RecordError {}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Node {
+    /// Move leading to the node, `None` for the starting position.
+    mv: Option<Move>,
+    parent: Option<usize>,
+    /// Continuations, the first of which is the main one.
+    children: Vec<usize>,
+    /// Position after the move.
+    position: Position,
+    ply: usize,
+    comment: Option<String>,
+    eval: Option<Score>,
+}
+
+/// A game with its metadata, the main line, variations and annotations,
+/// together with a cursor pointing at one of its positions.
+///
+/// The text format puts `[Name "value"]` headers before the moves, which
+/// are written in the move notation. Comments follow the move they belong
+/// to in braces, evaluations as `[eval 12]`, `[eval win 5]` or
+/// `[eval loss 4]`, and variations in parentheses after the main move
+/// they replace, e.g. `1. b3b2 {sharp} 2. c1b2 (2. a1a2 [eval -80])`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GameRecord {
+    /// Metadata such as the players or the date, in the order they were given.
+    pub headers: Vec<(String, String)>,
+    pub result: GameResult,
+    nodes: Vec<Node>,
+    current: usize,
+}
+
+impl GameRecord {
+    pub fn new(start: Position) -> Self {
+        GameRecord {
+            headers: vec![],
+            result: GameResult::Ongoing,
+            nodes: vec![Node {
+                mv: None,
+                parent: None,
+                children: vec![],
+                position: start,
+                ply: 0,
+                comment: None,
+                eval: None,
+            }],
+            current: 0,
+        }
+    }
+
+    /// Record of the moves played from `start`, with the cursor at the end.
+    pub fn from_moves(start: Position, moves: &[Move]) -> Result<Self, MoveError> {
+        let mut record = GameRecord::new(start);
+        for &mv in moves {
+            record.play(mv)?;
+        }
+        record.result = record.game_state().result();
+        Ok(record)
+    }
+
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(header, _)| header == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    pub fn set_header(&mut self, name: &str, value: &str) {
+        match self.headers.iter_mut().find(|(header, _)| header == name) {
+            Some((_, old)) => *old = value.to_owned(),
+            None => self.headers.push((name.to_owned(), value.to_owned())),
+        }
+    }
+
+    pub fn start(&self) -> &Position {
+        &self.nodes[0].position
+    }
+
+    /// Position at the cursor.
+    pub fn position(&self) -> &Position {
+        &self.nodes[self.current].position
+    }
+
+    /// Number of moves played to reach the cursor.
+    pub fn ply(&self) -> usize {
+        self.nodes[self.current].ply
+    }
+
+    /// Move which led to the cursor, `None` at the start.
+    pub fn last_move(&self) -> Option<Move> {
+        self.nodes[self.current].mv
+    }
+
+    /// Moves from the start to the cursor.
+    pub fn moves(&self) -> Vec<Move> {
+        let mut moves = vec![];
+        let mut node = self.current;
+        while let Some(parent) = self.nodes[node].parent {
+            moves.push(self.nodes[node].mv.unwrap());
+            node = parent;
+        }
+        moves.reverse();
+        moves
+    }
+
+    /// Moves of the main line, from the start to its end.
+    pub fn mainline(&self) -> Vec<Move> {
+        let mut moves = vec![];
+        let mut node = 0;
+        while let Some(&child) = self.nodes[node].children.first() {
+            moves.push(self.nodes[child].mv.unwrap());
+            node = child;
+        }
+        moves
+    }
+
+    /// The game from the start to the cursor, for detecting repetitions.
+    pub fn game_state(&self) -> GameState {
+        let mut game = GameState::new(*self.start());
+        for mv in self.moves() {
+            game.make_move(mv);
+        }
+        game
+    }
+
+    /// Continuations of the cursor, the main one first.
+    pub fn variations(&self) -> Vec<Move> {
+        self.nodes[self.current]
+            .children
+            .iter()
+            .map(|&child| self.nodes[child].mv.unwrap())
+            .collect()
+    }
+
+    /// Plays the move at the cursor, following it if it is already in the
+    /// record or adding it as the last variation otherwise. Fails if the
+    /// game has already ended at the cursor.
+    pub fn play(&mut self, mv: Move) -> Result<(), MoveError> {
+        if self.game_state().result() != GameResult::Ongoing {
+            return Err(MoveError::GameOver { mv });
+        }
+        if let Some(&child) = self.nodes[self.current]
+            .children
+            .iter()
+            .find(|&&child| self.nodes[child].mv == Some(mv))
+        {
+            self.current = child;
+            return Ok(());
+        }
+
+        let parent = &self.nodes[self.current];
+        if !parent.position.legal_moves().contains(&mv) {
+            return Err(MoveError::Illegal { mv });
+        }
+        let mut position = parent.position;
+        position.make_move(mv);
+        let node = Node {
+            mv: Some(mv),
+            parent: Some(self.current),
+            children: vec![],
+            position,
+            ply: parent.ply + 1,
+            comment: None,
+            eval: None,
+        };
+
+        let id = self.nodes.len();
+        self.nodes.push(node);
+        self.nodes[self.current].children.push(id);
+        self.current = id;
+        Ok(())
+    }
+
+    /// Moves the cursor along the main continuation.
+    pub fn forward(&mut self) -> bool {
+        self.enter_variation(0)
+    }
+
+    /// Moves the cursor along the given continuation, 0 being the main one.
+    pub fn enter_variation(&mut self, index: usize) -> bool {
+        match self.nodes[self.current].children.get(index) {
+            Some(&child) => {
+                self.current = child;
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn back(&mut self) -> bool {
+        match self.nodes[self.current].parent {
+            Some(parent) => {
+                self.current = parent;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Moves the cursor back along the current line or forward along the
+    /// main continuations until the given ply. Stops at the end of the line
+    /// and returns whether the ply was reached.
+    pub fn goto_ply(&mut self, ply: usize) -> bool {
+        while self.ply() > ply {
+            self.back();
+        }
+        while self.ply() < ply {
+            if !self.forward() {
+                return false;
+            }
+        }
+        true
+    }
+
+    pub fn goto_start(&mut self) {
+        self.current = 0;
+    }
+
+    /// Moves the cursor to the end of the main continuation.
+    pub fn goto_end(&mut self) {
+        while self.forward() {}
+    }
+
+    /// Makes the line leading to the cursor the main line.
+    /// Returns whether anything changed.
+    pub fn promote_variation(&mut self) -> bool {
+        let mut changed = false;
+        let mut node = self.current;
+        while let Some(parent) = self.nodes[node].parent {
+            let children = &mut self.nodes[parent].children;
+            let index = children.iter().position(|&child| child == node).unwrap();
+            if index > 0 {
+                let child = children.remove(index);
+                children.insert(0, child);
+                changed = true;
+            }
+            node = parent;
+        }
+        changed
+    }
+
+    /// Comment on the move which led to the cursor,
+    /// or on the game itself at the start.
+    pub fn comment(&self) -> Option<&str> {
+        self.nodes[self.current].comment.as_deref()
+    }
+
+    pub fn set_comment(&mut self, comment: Option<&str>) {
+        self.nodes[self.current].comment = comment.map(str::to_owned);
+    }
+
+    /// Evaluation of the position at the cursor, for the side to play.
+    pub fn eval(&self) -> Option<Score> {
+        self.nodes[self.current].eval
+    }
+
+    pub fn set_eval(&mut self, eval: Option<Score>) {
+        self.nodes[self.current].eval = eval;
+    }
+
+    fn annotations(&self, node: usize, tokens: &mut Vec<String>) {
+        let node = &self.nodes[node];
+        if let Some(comment) = &node.comment {
+            let escaped = comment.replace('\\', "\\\\").replace('}', "\\}");
+            tokens.push(format!("{{{}}}", escaped));
+        }
+        if let Some(eval) = node.eval {
+            tokens.push(match eval {
+                Score::Value(value) => format!("[eval {}]", value),
+                Score::Win(plies) => format!("[eval win {}]", plies),
+                Score::Loss(plies) => format!("[eval loss {}]", plies),
+            });
+        }
+    }
+
+    fn write_move(&self, node: usize, tokens: &mut Vec<String>) {
+        let Node {
+            mv, parent, ply, ..
+        } = &self.nodes[node];
+        let position = &self.nodes[parent.unwrap()].position;
+        tokens.push(format!("{}.", ply));
+        tokens.push(position.move_notation(mv.unwrap()));
+        self.annotations(node, tokens);
+    }
+
+    /// Writes the continuations of the node, with the variations of every
+    /// move right after it.
+    fn write_line(&self, mut node: usize, tokens: &mut Vec<String>) {
+        while let Some((&main, others)) = self.nodes[node].children.split_first() {
+            self.write_move(main, tokens);
+            for &other in others {
+                tokens.push("(".to_owned());
+                self.write_move(other, tokens);
+                self.write_line(other, tokens);
+                tokens.push(")".to_owned());
+            }
+            node = main;
+        }
+    }
+}
+
+fn quote(value: &str) -> String {
+    format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+impl fmt::Display for GameRecord {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (name, value) in &self.headers {
+            writeln!(f, "[{} {}]", name, quote(value))?;
+        }
+        // The header is left out only for the usual start of a game.
+        let start = self.start();
+        let mut startpos = Position::startpos();
+        startpos.set_move_number(start.move_number());
+        if *start != startpos || !matches!(start.move_number(), None | Some(1)) {
+            writeln!(f, "[{} {}]", SFEN_HEADER, quote(&start.sfen()))?;
+        }
+        writeln!(f, "[{} {}]", RESULT_HEADER, quote(result_name(self.result)))?;
+
+        let mut tokens = vec![];
+        self.annotations(0, &mut tokens);
+        self.write_line(0, &mut tokens);
+
+        let mut line = String::new();
+        for token in tokens {
+            let glued = token == ")" || line.ends_with('(');
+            if !line.is_empty() && !glued && line.len() + 1 + token.len() > LINE_WIDTH {
+                writeln!(f, "{}", line)?;
+                line.clear();
+            } else if !line.is_empty() && !glued {
+                line.push(' ');
+            }
+            line += &token;
+        }
+        if !line.is_empty() {
+            writeln!(f, "{}", line)?;
+        }
+        Ok(())
+    }
+}
+
+/// Reads text up to the closing delimiter, undoing backslash escapes.
+/// Returns the text and the number of newlines in it.
+fn read_escaped(
+    chars: &mut std::iter::Peekable<std::str::Chars>,
+    close: char,
+) -> Option<(String, usize)> {
+    let mut text = String::new();
+    let mut newlines = 0;
+    loop {
+        let c = chars.next()?;
+        if c == '\n' {
+            newlines += 1;
+        }
+        match c {
+            '\\' => text.push(chars.next()?),
+            c if c == close => return Some((text, newlines)),
+            c => text.push(c),
+        }
+    }
+}
+
+/// Whether the bracket at the front is an evaluation like `[eval 12]`
+/// rather than a header like `[Name "value"]`.
+fn is_eval(chars: &std::iter::Peekable<std::str::Chars>) -> bool {
+    let ahead: String = chars.clone().skip(1).take(6).collect();
+    ahead.starts_with("eval ") && !ahead.ends_with('"')
+}
+
+fn parse_eval(text: &str) -> Option<Score> {
+    let words: Vec<&str> = text.split_whitespace().collect();
+    match words[..] {
+        ["eval", value] => value.parse().ok().map(Score::Value),
+        ["eval", "win", plies] => plies.parse().ok().map(Score::Win),
+        ["eval", "loss", plies] => plies.parse().ok().map(Score::Loss),
+        _ => None,
+    }
+}
+
+impl FromStr for GameRecord {
+    type Err = RecordError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut record = GameRecord::new(Position::startpos());
+        let mut line = 1;
+        let mut in_headers = true;
+        let mut variations = vec![];
+        let mut chars = s.chars().peekable();
+
+        while let Some(&c) = chars.peek() {
+            let syntax = |expected| RecordError::Syntax { line, expected };
+            match c {
+                '\n' => {
+                    line += 1;
+                    chars.next();
+                }
+                c if c.is_whitespace() => {
+                    chars.next();
+                }
+                '[' if in_headers && !is_eval(&chars) => {
+                    chars.next();
+                    let name: String = chars.by_ref().take_while(|&c| c != ' ').collect();
+                    if chars.next() != Some('"') {
+                        return Err(syntax("a header like '[Name \"value\"]'"));
+                    }
+                    let (value, newlines) =
+                        read_escaped(&mut chars, '"').ok_or(syntax("a closing quote"))?;
+                    if chars.next() != Some(']') {
+                        return Err(syntax("']' after the header value"));
+                    }
+                    match name.as_str() {
+                        SFEN_HEADER => {
+                            let start = parse_sfen(&value)
+                                .map_err(|error| RecordError::Sfen { line, error })?;
+                            record.nodes[0].position = start;
+                        }
+                        RESULT_HEADER => {
+                            record.result = parse_result(&value)
+                                .ok_or(syntax("a result of '1-0', '0-1', '1/2-1/2' or '*'"))?;
+                        }
+                        _ => record.set_header(&name, &value),
+                    }
+                    line += newlines;
+                }
+                '[' => {
+                    in_headers = false;
+                    chars.next();
+                    let (text, newlines) = read_escaped(&mut chars, ']').ok_or(syntax("']'"))?;
+                    let eval = parse_eval(&text).ok_or(syntax("an evaluation like '[eval 12]'"))?;
+                    record.set_eval(Some(eval));
+                    line += newlines;
+                }
+                '{' => {
+                    in_headers = false;
+                    chars.next();
+                    let (comment, newlines) = read_escaped(&mut chars, '}').ok_or(syntax("'}'"))?;
+                    record.set_comment(Some(&comment));
+                    line += newlines;
+                }
+                '(' => {
+                    chars.next();
+                    variations.push(record.current);
+                    if !record.back() {
+                        return Err(syntax("a move before the variation"));
+                    }
+                }
+                ')' => {
+                    chars.next();
+                    record.current = variations.pop().ok_or(syntax("a variation to close"))?;
+                }
+                _ => {
+                    in_headers = false;
+                    let mut word = String::new();
+                    while let Some(&c) = chars.peek() {
+                        if c.is_whitespace() || "{}()[]".contains(c) {
+                            break;
+                        }
+                        word.push(c);
+                        chars.next();
+                    }
+                    let notation = word.trim_start_matches(|c: char| c.is_ascii_digit());
+                    let notation = notation.strip_prefix('.').unwrap_or(&word);
+                    if notation.is_empty() {
+                        continue;
+                    }
+                    let mv = record
+                        .position()
+                        .parse_move(notation)
+                        .map_err(|error| RecordError::Move { line, error })?;
+                    if record.variations().contains(&mv) && !variations.is_empty() {
+                        return Err(syntax("a variation different from the main move"));
+                    }
+                    record
+                        .play(mv)
+                        .map_err(|error| RecordError::Move { line, error })?;
+                }
+            }
+        }
+
+        if !variations.is_empty() {
+            return Err(RecordError::Syntax {
+                line,
+                expected: "')'",
+            });
+        }
+        record.goto_start();
+        Ok(record)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse_moves(position: &Position, notation: &[&str]) -> Vec<Move> {
+        let mut position = *position;
+        notation
+            .iter()
+            .map(|text| {
+                let mv = position.parse_move(text).unwrap();
+                position.make_move(mv);
+                mv
+            })
+            .collect()
+    }
+
+    fn sample() -> GameRecord {
+        let start = Position::startpos();
+        let mainline = parse_moves(&start, &["b3b2", "c1b2", "P*c2", "b2c3"]);
+        let mut record = GameRecord::from_moves(start, &mainline).unwrap();
+        record.set_header("Black", "Alice");
+        record.set_header("White", "Bob \"the lion\"");
+        record.result = GameResult::WhiteWin;
+        record.goto_start();
+        record.set_eval(Some(Score::Value(5)));
+
+        record.goto_ply(1);
+        record.set_comment(Some("the only sensible start"));
+        record.set_eval(Some(Score::Value(120)));
+        record.play("b1a2".parse().unwrap()).unwrap();
+        record.set_comment(Some("keeps material {for now}"));
+        record.play("b2b1+".parse().unwrap()).unwrap();
+        record.set_eval(Some(Score::Win(3)));
+        record.goto_start();
+        record.goto_ply(2);
+        record.play("c2c3".parse().unwrap()).unwrap_err();
+        record.play("b4c3".parse().unwrap()).unwrap();
+        record
+    }
+
+    const SAMPLE: &str = r#"[Black "Alice"]
+[White "Bob \"the lion\""]
+[Result "0-1"]
+[eval 5] 1. b3b2 {the only sensible start} [eval 120] 2. c1b2 (2. b1a2
+{keeps material {for now\}} 3. b2b1+ [eval win 3]) 3. P*c2 (3. b4c3) 4. b2c3
+"#;
+
+    #[test]
+    fn test_write() {
+        assert_eq!(sample().to_string(), SAMPLE);
+    }
+
+    #[test]
+    fn test_read() {
+        let record: GameRecord = SAMPLE.parse().unwrap();
+
+        assert_eq!(record.to_string(), SAMPLE);
+        assert_eq!(record.header("White"), Some("Bob \"the lion\""));
+        assert_eq!(record.result, GameResult::WhiteWin);
+        assert_eq!(record.mainline(), sample().mainline());
+        assert_eq!(record.ply(), 0);
+        assert_eq!(record.eval(), Some(Score::Value(5)));
+    }
+
+    #[test]
+    fn test_navigation() {
+        let mut record = sample();
+        record.goto_start();
+        assert!(!record.back());
+
+        assert!(record.forward());
+        assert_eq!(record.comment(), Some("the only sensible start"));
+        assert_eq!(record.eval(), Some(Score::Value(120)));
+        assert_eq!(record.variations().len(), 2);
+
+        assert!(record.enter_variation(1));
+        assert_eq!(record.last_move().unwrap().to_string(), "b1a2");
+        assert!(record.goto_ply(3));
+        assert_eq!(record.moves().len(), 3);
+        assert_eq!(record.position().to_string(), "rP+b/k2/3/BKR w P");
+        assert!(!record.forward());
+
+        assert!(record.goto_ply(1));
+        assert!(!record.goto_ply(10));
+        assert_eq!(record.ply(), 4);
+        assert_eq!(record.moves(), record.mainline());
+    }
+
+    #[test]
+    fn test_promote_variation() {
+        let mut record = sample();
+        record.goto_start();
+        record.forward();
+        record.enter_variation(1);
+        record.forward();
+
+        assert!(record.promote_variation());
+        assert!(!record.promote_variation());
+        assert_eq!(record.mainline(), record.moves());
+        assert_eq!(record.ply(), 3);
+    }
+
+    #[test]
+    fn test_game_state() {
+        let mut record = GameRecord::new(Position::startpos());
+        for _ in 0..2 {
+            for text in ["b4a3", "b1a2", "a3b4", "a2b1"] {
+                record.play(text.parse().unwrap()).unwrap();
+            }
+        }
+
+        assert_eq!(record.game_state().result(), GameResult::Draw);
+        assert_eq!(record.nodes.len(), 9);
+    }
+
+    #[test]
+    fn test_custom_start() {
+        let start = parse_sfen("1k1/P2/3/1K1 b pb2r2 5").unwrap();
+        let mut record = GameRecord::new(start);
+        record.set_comment(Some("white to defend"));
+        record.play(start.parse_move("a2a1").unwrap()).unwrap();
+        let text = record.to_string();
+
+        assert_eq!(
+            text,
+            "[Sfen \"1k1/P2/3/1K1 b pb2r2 5\"]\n[Result \"*\"]\n{white to defend} 1. a2a1+\n"
+        );
+        let read: GameRecord = text.parse().unwrap();
+        assert_eq!(read.start(), &start);
+        assert_eq!(read.comment(), Some("white to defend"));
+        assert_eq!(read.to_string(), text);
+    }
+
+    #[test]
+    fn test_startpos_header() {
+        let numbered = |move_number| {
+            let mut start = Position::startpos();
+            start.set_move_number(move_number);
+            GameRecord::new(start).to_string()
+        };
+
+        assert_eq!(numbered(None), "[Result \"*\"]\n");
+        assert_eq!(numbered(Some(1)), "[Result \"*\"]\n");
+        assert_eq!(
+            numbered(Some(3)),
+            "[Sfen \"rkb/1p1/1P1/BKR b - 3\"]\n[Result \"*\"]\n"
+        );
+        let read: GameRecord = numbered(Some(3)).parse().unwrap();
+        assert_eq!(read.start().move_number(), Some(3));
+    }
+
+    #[test]
+    fn test_errors() {
+        let cases = [
+            ("[Black Alice]", 1, "a header like '[Name \"value\"]'"),
+            (
+                "[Result \"2-0\"]",
+                1,
+                "a result of '1-0', '0-1', '1/2-1/2' or '*'",
+            ),
+            (
+                "1. b3b2\n(1. b3b2)",
+                2,
+                "a variation different from the main move",
+            ),
+            ("(1. b3b2)", 1, "a move before the variation"),
+            ("1. b3b2 )", 1, "a variation to close"),
+            ("1. b3b2 (1. b4a3", 1, "')'"),
+            ("1. b3b2 [eval good]", 1, "an evaluation like '[eval 12]'"),
+            ("1. b3b2 {unfinished", 1, "'}'"),
+        ];
+
+        for (text, line, expected) in cases {
+            assert_eq!(
+                text.parse::<GameRecord>(),
+                Err(RecordError::Syntax { line, expected }),
+                "{}",
+                text
+            );
+        }
+
+        assert_eq!(
+            "1. b3b2\n2. b3b1".parse::<GameRecord>(),
+            Err(RecordError::Move {
+                line: 2,
+                error: MoveError::Illegal {
+                    mv: Move::Board { from: 7, to: 1 }
+                }
+            })
+        );
+        assert_eq!(
+            "1. b3b2 2. a1a2 3. b2b1+ 4. a2a3".parse::<GameRecord>(),
+            Err(RecordError::Move {
+                line: 1,
+                error: MoveError::GameOver {
+                    mv: Move::Board { from: 3, to: 6 }
+                }
+            })
+        );
+    }
+}
@@ -0,0 +1,271 @@
+use crate::game::*;
+use crate::notation::field_name;
+use crate::sfen::Sfen;
+
+const RESET: &str = "\x1b[0m";
+/// Resets the bold text and the foreground color only, so that pieces
+/// keep the background of highlighted squares.
+const RESET_FOREGROUND: &str = "\x1b[22;39m";
+const BLACK_PIECE: &str = "\x1b[1;34m";
+const WHITE_PIECE: &str = "\x1b[1;31m";
+const LAST_MOVE: &str = "\x1b[43m";
+const ATTACKED: &str = "\x1b[45m";
+
+/// How pieces are drawn.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub enum Glyphs {
+    /// SFEN letters, upper case for Black.
+    #[default]
+    Ascii,
+    /// The first kana of the Japanese names, White's marked with `v`.
+    Kana,
+    /// Animal emoji, White's marked with `v`.
+    Emoji,
+}
+
+impl Glyphs {
+    fn piece(self, piece: Piece) -> String {
+        let glyph = match (self, piece.kind) {
+            (Glyphs::Ascii, _) => return piece.sfen(),
+            (Glyphs::Kana, PieceKind::Pawn) => "ひ",
+            (Glyphs::Kana, PieceKind::Bishop) => "ぞ",
+            (Glyphs::Kana, PieceKind::Rook) => "き",
+            (Glyphs::Kana, PieceKind::King) => "ラ",
+            (Glyphs::Kana, PieceKind::PromotedPawn) => "に",
+            (Glyphs::Emoji, PieceKind::Pawn) => "🐤",
+            (Glyphs::Emoji, PieceKind::Bishop) => "🐘",
+            (Glyphs::Emoji, PieceKind::Rook) => "🦒",
+            (Glyphs::Emoji, PieceKind::King) => "🦁",
+            (Glyphs::Emoji, PieceKind::PromotedPawn) => "🐔",
+        };
+        glyph.to_owned()
+    }
+
+    /// The piece, three columns wide.
+    fn cell(self, piece: Piece) -> String {
+        match self {
+            Glyphs::Ascii => format!("{:^3}", self.piece(piece)),
+            _ => {
+                let mark = match piece.color {
+                    Color::Black => ' ',
+                    Color::White => 'v',
+                };
+                format!("{}{}", mark, self.piece(piece))
+            }
+        }
+    }
+
+    fn hand_piece(self, piece: HandPiece, color: Color) -> String {
+        self.piece(Piece {
+            kind: piece.into(),
+            color,
+        })
+    }
+}
+
+/// Draws boards and positions as framed text diagrams with file and rank
+/// labels. Squares of the last move are marked with `[ ]` and squares
+/// attacked by the chosen side with `( )`, or with background colors
+/// when ANSI colors are enabled.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub struct Renderer {
+    glyphs: Glyphs,
+    ansi: bool,
+    last_move: Option<Move>,
+    attacked_by: Option<Color>,
+}
+
+impl Renderer {
+    pub fn new(glyphs: Glyphs) -> Self {
+        Renderer {
+            glyphs,
+            ..Renderer::default()
+        }
+    }
+
+    /// Colors the pieces of each side and the highlighted squares.
+    pub fn with_ansi(mut self, ansi: bool) -> Self {
+        self.ansi = ansi;
+        self
+    }
+
+    pub fn with_last_move(mut self, last_move: Option<Move>) -> Self {
+        self.last_move = last_move;
+        self
+    }
+
+    /// Highlights the squares attacked by the given side.
+    pub fn with_attacked_by(mut self, attacked_by: Option<Color>) -> Self {
+        self.attacked_by = attacked_by;
+        self
+    }
+
+    fn paint(&self, text: &str, code: &str) -> String {
+        if self.ansi {
+            format!("{}{}{}", code, text, RESET_FOREGROUND)
+        } else {
+            text.to_owned()
+        }
+    }
+
+    fn side_name(&self, color: Color) -> String {
+        match color {
+            Color::Black => self.paint("Black", BLACK_PIECE),
+            Color::White => self.paint("White", WHITE_PIECE),
+        }
+    }
+
+    pub fn render_board(&self, board: &Board) -> String {
+        let bitboard = BitBoard::from(board);
+        let last_move_fields = match self.last_move {
+            Some(Move::Board { from, to }) => vec![from, to],
+            Some(Move::Drop { to, .. }) => vec![to],
+            None => vec![],
+        };
+
+        let separator = format!("  +{}", "-----+".repeat(COLS));
+        let mut lines = vec![];
+        let labels: String = (0..COLS)
+            .map(|col| format!("{:^6}", &field_name(col)[..1]))
+            .collect();
+        lines.push(format!("   {}", labels).trim_end().to_owned());
+        lines.push(separator.clone());
+
+        for row in 0..ROWS {
+            let mut line = format!("{} |", row + 1);
+            for col in 0..COLS {
+                let field = rowcol2field(row, col);
+                let body = match board[field] {
+                    Some(piece) => {
+                        let code = match piece.color {
+                            Color::Black => BLACK_PIECE,
+                            Color::White => WHITE_PIECE,
+                        };
+                        self.paint(&self.glyphs.cell(piece), code)
+                    }
+                    None => "   ".to_owned(),
+                };
+
+                let last_move = last_move_fields.contains(&field);
+                let attacked = self
+                    .attacked_by
+                    .is_some_and(|color| bitboard.attackers(field, color) != 0);
+                let cell = match (last_move, attacked, self.ansi) {
+                    (true, _, true) => format!("{} {} {}", LAST_MOVE, body, RESET),
+                    (false, true, true) => format!("{} {} {}", ATTACKED, body, RESET),
+                    (true, _, false) => format!("[{}]", body),
+                    (false, true, false) => format!("({})", body),
+                    (false, false, _) => format!(" {} ", body),
+                };
+                line += &cell;
+                line.push('|');
+            }
+            lines.push(line);
+            lines.push(separator.clone());
+        }
+        lines.join("\n")
+    }
+
+    fn render_hand(&self, position: &Position, color: Color) -> String {
+        let pieces: Vec<String> = position
+            .hand(color)
+            .iter()
+            .flat_map(|(piece, count)| {
+                std::iter::repeat_n(self.glyphs.hand_piece(piece, color), count)
+            })
+            .collect();
+        let pieces = if pieces.is_empty() {
+            "-".to_owned()
+        } else {
+            pieces.join(" ")
+        };
+        format!("{} hand: {}", self.side_name(color), pieces)
+    }
+
+    /// The board between the hands of White above and Black below,
+    /// followed by the side to play.
+    pub fn render(&self, position: &Position) -> String {
+        [
+            self.render_hand(position, Color::White),
+            self.render_board(position.board()),
+            self.render_hand(position, Color::Black),
+            format!("{} to play", self.side_name(position.to_play())),
+        ]
+        .join("\n")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse_sfen;
+
+    #[test]
+    fn test_ascii() {
+        let position = parse_sfen("rk1/1b1/3/BKR b Pp").unwrap();
+
+        assert_eq!(
+            Renderer::new(Glyphs::Ascii).render(&position),
+            "\
+White hand: p
+     a     b     c
+  +-----+-----+-----+
+1 |  r  |  k  |     |
+  +-----+-----+-----+
+2 |     |  b  |     |
+  +-----+-----+-----+
+3 |     |     |     |
+  +-----+-----+-----+
+4 |  B  |  K  |  R  |
+  +-----+-----+-----+
+Black hand: P
+Black to play"
+        );
+    }
+
+    #[test]
+    fn test_kana() {
+        let position = parse_sfen("1k1/3/1P+1/1K1 w BRbpr").unwrap();
+        let text = Renderer::new(Glyphs::Kana).render(&position);
+
+        assert!(text.starts_with("White hand: ひ ぞ き\n"));
+        assert!(text.contains("\n1 |     | vラ |     |\n"));
+        assert!(text.contains("\n3 |     |  に |     |\n"));
+        assert!(text.ends_with("Black hand: ぞ き\nWhite to play"));
+    }
+
+    #[test]
+    fn test_emoji() {
+        let text = Renderer::new(Glyphs::Emoji).render_board(Position::startpos().board());
+
+        assert!(text.contains("\n1 | v🦒 | v🦁 | v🐘 |\n"));
+        assert!(text.contains("\n3 |     |  🐤 |     |\n"));
+    }
+
+    #[test]
+    fn test_last_move_and_attacks() {
+        // The piece on a highlighted square keeps the background.
+        let mv = Move::Board { from: 7, to: 4 };
+        let mut position = Position::startpos();
+        position.make_move(mv);
+        let captured = Renderer::new(Glyphs::Ascii)
+            .with_ansi(true)
+            .with_last_move(Some(mv))
+            .render_board(position.board());
+        assert!(captured.contains("|\x1b[43m \x1b[1;34m P \x1b[22;39m \x1b[0m|"));
+
+        let plain = Renderer::new(Glyphs::Ascii)
+            .with_attacked_by(Some(Color::Black))
+            .render_board(Position::startpos().board());
+        assert!(plain.contains("\n1 |  r  |  k  |  b  |\n"));
+        assert!(plain.contains("\n2 |     |( p )|     |\n"));
+        assert!(plain.contains("\n3 |(   )|( P )|(   )|\n"));
+
+        let colored = Renderer::new(Glyphs::Ascii)
+            .with_ansi(true)
+            .with_attacked_by(Some(Color::Black))
+            .render_board(Position::startpos().board());
+        assert!(colored.contains("\n2 |     |\x1b[45m \x1b[1;31m p \x1b[22;39m \x1b[0m|     |\n"));
+        assert!(colored.contains("\n3 |\x1b[45m     \x1b[0m|"));
+    }
+}
@@ -0,0 +1,575 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use crate::eval::{evaluate_with, Weights};
+use crate::game::*;
+use crate::tt::{Bound, TranspositionTable};
+
+/// Score of winning right now. Wins further away score less,
+/// so that the search prefers the fastest win and the slowest loss.
+pub const WIN_SCORE: i32 = 30_000;
+/// Scores above this (or below its negation) are wins (or losses)
+/// at a known distance.
+pub const WIN_THRESHOLD: i32 = WIN_SCORE - 1_000;
+const INFINITY: i32 = WIN_SCORE + 1;
+
+pub const MAX_DEPTH: usize = 64;
+
+/// How often, in nodes, the time limit is checked.
+const CHECK_INTERVAL: u64 = 1024;
+
+/// Score of a position from the point of view of the side to play.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Score {
+    /// Static score, in hundredths of a chick.
+    Value(i32),
+    /// The side to play wins in the given number of plies.
+    Win(u32),
+    /// The side to play loses in the given number of plies.
+    Loss(u32),
+}
+
+impl Score {
+    pub fn from_value(value: i32) -> Self {
+        if value > WIN_THRESHOLD {
+            Score::Win((WIN_SCORE - value) as u32)
+        } else if value < -WIN_THRESHOLD {
+            Score::Loss((WIN_SCORE + value) as u32)
+        } else {
+            Score::Value(value)
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub struct SearchLimits {
+    pub depth: Option<usize>,
+    pub nodes: Option<u64>,
+    pub time: Option<Duration>,
+}
+
+impl SearchLimits {
+    pub fn depth(depth: usize) -> Self {
+        SearchLimits {
+            depth: Some(depth),
+            ..Default::default()
+        }
+    }
+
+    pub fn nodes(nodes: u64) -> Self {
+        SearchLimits {
+            nodes: Some(nodes),
+            ..Default::default()
+        }
+    }
+
+    pub fn time(time: Duration) -> Self {
+        SearchLimits {
+            time: Some(time),
+            ..Default::default()
+        }
+    }
+}
+
+/// Outcome of the last fully searched iteration.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SearchResult {
+    pub best_move: Option<Move>,
+    pub score: i32,
+    pub pv: Vec<Move>,
+    pub depth: usize,
+    pub nodes: u64,
+    pub time: Duration,
+}
+
+impl SearchResult {
+    pub fn score(&self) -> Score {
+        Score::from_value(self.score)
+    }
+}
+
+/// Iterative deepening negamax search with alpha-beta pruning.
+pub struct Searcher {
+    limits: SearchLimits,
+    stop: Arc<AtomicBool>,
+    /// Keys of the positions played before the searched one,
+    /// followed by the keys of the positions on the current search path.
+    history: Vec<u64>,
+    /// Number of keys in `history` played in the game.
+    game_plies: usize,
+    nodes: u64,
+    start: Instant,
+    aborted: bool,
+    tt: TranspositionTable,
+    weights: Weights,
+}
+
+impl Searcher {
+    pub fn new(limits: SearchLimits) -> Self {
+        Searcher {
+            limits,
+            stop: Arc::new(AtomicBool::new(false)),
+            history: vec![],
+            game_plies: 0,
+            nodes: 0,
+            start: Instant::now(),
+            aborted: false,
+            tt: TranspositionTable::default(),
+            weights: Weights::default(),
+        }
+    }
+
+    /// Weights of the evaluation function used at the leaves.
+    pub fn with_weights(mut self, weights: Weights) -> Self {
+        self.weights = weights;
+        self
+    }
+
+    pub fn set_weights(&mut self, weights: Weights) {
+        self.weights = weights;
+    }
+
+    /// Replaces the transposition table by an empty one of the given size.
+    pub fn with_tt_size(mut self, size_mb: usize) -> Self {
+        self.tt = TranspositionTable::new(size_mb);
+        self
+    }
+
+    pub fn set_limits(&mut self, limits: SearchLimits) {
+        self.limits = limits;
+    }
+
+    /// Forgets everything learned in previous searches.
+    pub fn clear_tt(&mut self) {
+        self.tt.clear();
+    }
+
+    /// Flag which stops the search as soon as it is set.
+    pub fn with_stop(mut self, stop: Arc<AtomicBool>) -> Self {
+        self.stop = stop;
+        self
+    }
+
+    /// Keys of the positions which occurred in the game before, so that
+    /// reaching the repetition limit with them is scored as a draw.
+    pub fn with_history(mut self, history: &[u64]) -> Self {
+        self.set_history(history);
+        self
+    }
+
+    pub fn set_history(&mut self, history: &[u64]) {
+        self.history = history.to_vec();
+        self.game_plies = history.len();
+    }
+
+    pub fn search(&mut self, position: &Position) -> SearchResult {
+        self.search_with_info(position, |_| {})
+    }
+
+    /// Searches the position, calling `info` after each completed iteration.
+    pub fn search_with_info(
+        &mut self,
+        position: &Position,
+        mut info: impl FnMut(&SearchResult),
+    ) -> SearchResult {
+        self.nodes = 0;
+        self.start = Instant::now();
+        self.aborted = false;
+        self.tt.new_search();
+
+        let mut position = *position;
+        let mut result = SearchResult {
+            best_move: position.legal_moves().first().copied(),
+            score: 0,
+            pv: vec![],
+            depth: 0,
+            nodes: 0,
+            time: Duration::ZERO,
+        };
+
+        let max_depth = self.limits.depth.unwrap_or(MAX_DEPTH).min(MAX_DEPTH);
+        for depth in 1..=max_depth {
+            let (score, pv) =
+                self.negamax(&mut position, depth, 0, -INFINITY, INFINITY, &result.pv);
+            if self.aborted {
+                break;
+            }
+
+            result = SearchResult {
+                best_move: pv.first().copied().or(result.best_move),
+                score,
+                pv,
+                depth,
+                nodes: self.nodes,
+                time: self.start.elapsed(),
+            };
+            info(&result);
+
+            if score.abs() > WIN_THRESHOLD && (WIN_SCORE - score.abs()) as usize <= depth {
+                break;
+            }
+        }
+
+        result.nodes = self.nodes;
+        result.time = self.start.elapsed();
+        result
+    }
+
+    /// Checks the limits, reading the clock only if `check_time` is set.
+    fn should_stop(&mut self, check_time: bool) -> bool {
+        if self.aborted {
+            return true;
+        }
+        let out_of_nodes = self.limits.nodes.is_some_and(|nodes| self.nodes >= nodes);
+        let out_of_time = check_time
+            && self
+                .limits
+                .time
+                .is_some_and(|time| self.start.elapsed() >= time);
+        self.aborted = out_of_nodes || out_of_time || self.stop.load(Ordering::Relaxed);
+        self.aborted
+    }
+
+    /// Whether the position repeats one on the search path, which the side
+    /// going for it could repeat until the game is drawn, or occurred often
+    /// enough in the game to be drawn right away.
+    fn is_repetition(&self, key: u64) -> bool {
+        let (game, path) = self.history.split_at(self.game_plies);
+        path.contains(&key)
+            || game.iter().filter(|&&other| other == key).count() + 1 >= DEFAULT_REPETITION_LIMIT
+    }
+
+    fn negamax(
+        &mut self,
+        position: &mut Position,
+        depth: usize,
+        ply: usize,
+        mut alpha: i32,
+        beta: i32,
+        pv_hint: &[Move],
+    ) -> (i32, Vec<Move>) {
+        self.nodes += 1;
+
+        match position.result() {
+            GameResult::Ongoing => {}
+            GameResult::Draw => return (0, vec![]),
+            result if result == GameResult::win(position.to_play()) => {
+                return (WIN_SCORE - ply as i32, vec![]);
+            }
+            _ => return (-(WIN_SCORE - ply as i32), vec![]),
+        }
+
+        // The lion of the side which just moved can be captured: the game
+        // ends on the next ply whatever else is going on.
+        if let Some(field) = position.king_field(position.to_play().opposite()) {
+            if position.is_attacked(field, position.to_play()) {
+                let capture = position
+                    .legal_moves()
+                    .into_iter()
+                    .find(|mv| mv.to() == field);
+                return (WIN_SCORE - ply as i32 - 1, capture.into_iter().collect());
+            }
+        }
+
+        if ply > 0 && self.is_repetition(position.key()) {
+            return (0, vec![]);
+        }
+
+        let key = position.key();
+        let tt_entry = self.tt.probe(key, ply);
+        if let Some(entry) = tt_entry.filter(|entry| ply > 0 && entry.depth as usize >= depth) {
+            let cutoff = match entry.bound {
+                Bound::Exact => true,
+                Bound::Lower => entry.score >= beta,
+                Bound::Upper => entry.score <= alpha,
+            };
+            if cutoff {
+                let pv = entry
+                    .best_move
+                    .filter(|mv| position.legal_moves().contains(mv));
+                return (entry.score, pv.into_iter().collect());
+            }
+        }
+
+        if depth == 0 || ply >= MAX_DEPTH {
+            return (evaluate_with(position, &self.weights), vec![]);
+        }
+
+        if self.should_stop(self.nodes.is_multiple_of(CHECK_INTERVAL)) {
+            return (0, vec![]);
+        }
+
+        let mut moves = position.legal_moves();
+        if moves.is_empty() {
+            return (-(WIN_SCORE - ply as i32), vec![]);
+        }
+        let best_guess = pv_hint
+            .first()
+            .copied()
+            .or(tt_entry.and_then(|entry| entry.best_move));
+        order_moves(position, &mut moves, best_guess, &self.weights);
+
+        let original_alpha = alpha;
+
+        let mut best_pv = vec![];
+        let mut best_score = -INFINITY;
+        self.history.push(key);
+
+        for mv in moves {
+            // Iterations at a low depth can take long, so the time is
+            // checked between root moves as well.
+            if ply == 0 && self.should_stop(true) {
+                break;
+            }
+            let undo = position.make_move(mv);
+            let hint = match pv_hint.split_first() {
+                Some((&first, rest)) if first == mv => rest,
+                _ => &[],
+            };
+            let (score, pv) = self.negamax(position, depth - 1, ply + 1, -beta, -alpha, hint);
+            let score = -score;
+            position.unmake_move(mv, undo);
+
+            if self.aborted {
+                break;
+            }
+
+            if score > best_score {
+                best_score = score;
+                best_pv = vec![mv];
+                best_pv.extend(pv);
+            }
+            if score > alpha {
+                alpha = score;
+            }
+            if alpha >= beta {
+                break;
+            }
+        }
+
+        self.history.pop();
+
+        if !self.aborted {
+            let bound = if best_score <= original_alpha {
+                Bound::Upper
+            } else if best_score >= beta {
+                Bound::Lower
+            } else {
+                Bound::Exact
+            };
+            self.tt
+                .store(key, depth, best_score, bound, best_pv.first().copied(), ply);
+        }
+
+        (best_score, best_pv)
+    }
+}
+
+/// Searches the position from scratch with the given limits.
+pub fn search(position: &Position, limits: SearchLimits) -> SearchResult {
+    Searcher::new(limits).search(position)
+}
+
+/// Puts the expected best move first, followed by captures
+/// of the most valuable pieces.
+fn order_moves(position: &Position, moves: &mut [Move], best: Option<Move>, weights: &Weights) {
+    moves.sort_by_key(|&mv| {
+        if Some(mv) == best {
+            return i32::MIN;
+        }
+        match mv {
+            Move::Board { to, .. } => {
+                -position.board()[to].map_or(0, |piece| piece_value(piece.kind, weights))
+            }
+            Move::Drop { .. } => 0,
+        }
+    });
+}
+
+fn piece_value(kind: PieceKind, weights: &Weights) -> i32 {
+    match kind {
+        PieceKind::King => 10_000,
+        kind => weights.piece(kind),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse_sfen;
+
+    #[test]
+    fn test_score_from_value() {
+        assert_eq!(Score::from_value(120), Score::Value(120));
+        assert_eq!(Score::from_value(WIN_SCORE - 3), Score::Win(3));
+        assert_eq!(Score::from_value(-WIN_SCORE + 4), Score::Loss(4));
+    }
+
+    #[test]
+    fn test_captures_the_lion() {
+        let position = parse_sfen("1k1/3/1R1/1K1 w P2B2r").unwrap();
+        let mut position = position;
+        position.make_move(Move::Board { from: 1, to: 4 });
+
+        let result = search(&position, SearchLimits::depth(3));
+
+        assert_eq!(result.best_move, Some(Move::Board { from: 7, to: 4 }));
+        assert_eq!(result.score(), Score::Win(1));
+    }
+
+    #[test]
+    fn test_finds_winning_try() {
+        // The lion walks to the last row where nothing can reach it.
+        let position = parse_sfen("2k/K2/1p1/2B b Pbr2").unwrap();
+
+        let result = search(&position, SearchLimits::depth(4));
+
+        assert_eq!(result.best_move, Some(Move::Board { from: 3, to: 0 }));
+        assert_eq!(result.score(), Score::Win(1));
+        assert_eq!(result.pv, vec![Move::Board { from: 3, to: 0 }]);
+    }
+
+    #[test]
+    fn test_avoids_losing_the_lion() {
+        // Taking the chick with the lion would walk into the other lion.
+        let position = parse_sfen("1kr/1p1/K2/3 b PB2R").unwrap();
+
+        let result = search(&position, SearchLimits::depth(3));
+
+        assert_ne!(result.best_move, Some(Move::Board { from: 6, to: 4 }));
+        assert!(!matches!(result.score(), Score::Loss(_)));
+    }
+
+    #[test]
+    fn test_wins_material() {
+        let position = parse_sfen("rkb/1p1/1P1/BKR b -").unwrap();
+
+        let result = search(&position, SearchLimits::depth(1));
+
+        assert_eq!(result.best_move, Some(Move::Board { from: 7, to: 4 }));
+        assert_eq!(result.pv.len(), 1);
+        assert_eq!(result.depth, 1);
+    }
+
+    #[test]
+    fn test_pv_is_playable() {
+        let position = Position::startpos();
+
+        let result = search(&position, SearchLimits::depth(5));
+
+        let mut replay = position;
+        for mv in &result.pv {
+            assert!(replay.legal_moves().contains(mv), "{:?}", result.pv);
+            replay.make_move(*mv);
+        }
+        assert_eq!(result.depth, 5);
+        assert!(result.nodes > 0);
+    }
+
+    #[test]
+    fn test_node_limit() {
+        let result = search(&Position::startpos(), SearchLimits::nodes(5_000));
+
+        assert!(result.nodes <= 5_000 + MAX_DEPTH as u64);
+        assert!(result.best_move.is_some());
+    }
+
+    #[test]
+    fn test_stop_flag() {
+        let stop = Arc::new(AtomicBool::new(true));
+        let mut searcher = Searcher::new(SearchLimits::default()).with_stop(stop);
+
+        let result = searcher.search(&Position::startpos());
+
+        assert_eq!(result.depth, 0);
+        assert!(result.best_move.is_some());
+    }
+
+    #[test]
+    fn test_time_limit() {
+        let limits = SearchLimits::time(Duration::from_millis(50));
+
+        let result = search(&Position::startpos(), limits);
+
+        assert!(result.time < Duration::from_secs(2));
+        assert!(result.best_move.is_some());
+    }
+
+    #[test]
+    fn test_repetition_scores_draw() {
+        // White is far behind, so going back is the best it can do.
+        let mut game = GameState::new(parse_sfen("k2/3/3/BKR b P2BR").unwrap());
+        let cycle = [
+            Move::Board { from: 10, to: 7 },
+            Move::Board { from: 0, to: 3 },
+            Move::Board { from: 7, to: 10 },
+            Move::Board { from: 3, to: 0 },
+        ];
+        for &mv in &cycle[..3] {
+            game.make_move(mv);
+        }
+
+        // Going back repeats the start only once, which is no draw yet.
+        let mut searcher = Searcher::new(SearchLimits::depth(1)).with_history(&game.previous_keys);
+        let once = searcher.search(&game.current_position);
+        assert!(once.score < 0);
+
+        for mv in cycle.into_iter().cycle().skip(3).take(4) {
+            game.make_move(mv);
+        }
+        let mut searcher = Searcher::new(SearchLimits::depth(1)).with_history(&game.previous_keys);
+        let twice = searcher.search(&game.current_position);
+        assert_eq!(twice.score(), Score::Value(0));
+    }
+
+    #[test]
+    fn test_repetitions() {
+        let mut searcher = Searcher::new(SearchLimits::depth(1)).with_history(&[1, 2, 1]);
+
+        assert!(searcher.is_repetition(1));
+        assert!(!searcher.is_repetition(2));
+        assert!(!searcher.is_repetition(3));
+
+        searcher.history.extend([3, 2]);
+        assert!(searcher.is_repetition(2));
+        assert!(searcher.is_repetition(3));
+    }
+
+    #[test]
+    fn test_info_called_per_iteration() {
+        let mut depths = vec![];
+        let mut searcher = Searcher::new(SearchLimits::depth(3));
+
+        searcher.search_with_info(&Position::startpos(), |info| depths.push(info.depth));
+
+        assert_eq!(depths, vec![1, 2, 3]);
+    }
+
+    #[test]
+    fn test_tt_keeps_results_between_searches() {
+        let position = Position::startpos();
+        let mut searcher = Searcher::new(SearchLimits::depth(6)).with_tt_size(1);
+
+        let first = searcher.search(&position);
+        let second = searcher.search(&position);
+
+        assert_eq!(first.score, second.score);
+        assert!(second.nodes < first.nodes);
+
+        searcher.clear_tt();
+        let third = searcher.search(&position);
+        assert_eq!(third.nodes, first.nodes);
+    }
+
+    #[test]
+    fn test_tt_does_not_change_win_distance() {
+        let position = parse_sfen("2k/K2/1p1/2B b Pbr2").unwrap();
+        let mut searcher = Searcher::new(SearchLimits::depth(6)).with_tt_size(1);
+
+        searcher.search(&position);
+        let result = searcher.search(&position);
+
+        assert_eq!(result.score(), Score::Win(1));
+    }
+}
@@ -1,6 +1,9 @@
+use std::fmt;
+
 use crate::game::*;
 
-trait Sfen {
+/// Rendering in the SFEN notation.
+pub trait Sfen {
     fn sfen(&self) -> String;
 }
 
@@ -82,48 +85,64 @@ impl Sfen for Board {
     }
 }
 
+impl Sfen for BitBoard {
+    fn sfen(&self) -> String {
+        Board::from(self).sfen()
+    }
+}
+
 impl Sfen for Hand {
     fn sfen(&self) -> String {
         let mut result = String::new();
 
-        for piece in ALL_HAND_PIECES {
-            if let Some(&count) = self.0.get(&piece) {
-                if count > 1 {
-                    result += &format!("{}{}", piece.sfen(), count);
-                } else if count == 1 {
-                    result += &piece.sfen();
-                }
+        for (piece, count) in self.iter() {
+            if count > 1 {
+                result += &format!("{}{}", piece.sfen(), count);
+            } else {
+                result += &piece.sfen();
             }
         }
-        return result;
+        result
     }
 }
 
 impl Sfen for Position {
     fn sfen(&self) -> String {
         let mut hands_sfen = String::new();
-        hands_sfen += &self.black_hand.sfen().to_ascii_uppercase();
-        hands_sfen += &self.white_hand.sfen();
+        hands_sfen += &self.hand(Color::Black).sfen().to_ascii_uppercase();
+        hands_sfen += &self.hand(Color::White).sfen();
 
-        format!(
+        let mut result = format!(
             "{} {} {}",
-            self.board.sfen(),
-            self.to_play.sfen(),
-            if hands_sfen == "" { "-" } else { &hands_sfen }
-        )
+            self.board().sfen(),
+            self.to_play().sfen(),
+            if hands_sfen.is_empty() {
+                "-"
+            } else {
+                &hands_sfen
+            }
+        );
+        if let Some(move_number) = self.move_number() {
+            result += &format!(" {}", move_number);
+        }
+        result
+    }
+}
+
+impl fmt::Display for Position {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.sfen())
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use std::collections::HashMap;
-
     use super::*;
 
     #[test]
     pub fn test_piece_kind() {
         use PieceKind::*;
-        let pieces = vec![Pawn, Bishop, Rook, King, PromotedPawn];
+        let pieces = [Pawn, Bishop, Rook, King, PromotedPawn];
         let result: Vec<String> = pieces.iter().map(|piece| piece.sfen()).collect();
 
         assert_eq!(result, vec!["p", "b", "r", "k", "p+"]);
@@ -131,7 +150,7 @@ mod tests {
 
     #[test]
     pub fn test_color() {
-        let colors = vec![Color::Black, Color::White];
+        let colors = [Color::Black, Color::White];
         let result: Vec<String> = colors.iter().map(|color| color.sfen()).collect();
 
         assert_eq!(result, vec!["b", "w"]);
@@ -199,7 +218,7 @@ mod tests {
 
     #[test]
     pub fn test_empty_hand() {
-        let hand = Hand(HashMap::new());
+        let hand = Hand::empty();
         let result = hand.sfen();
 
         assert_eq!(result, "");
@@ -207,12 +226,11 @@ mod tests {
 
     #[test]
     pub fn test_hand_all_one() {
-        let hashmap = HashMap::from([
+        let hand = Hand::from([
             (HandPiece::Bishop, 1),
             (HandPiece::Rook, 1),
             (HandPiece::Pawn, 1),
         ]);
-        let hand = Hand(hashmap);
         let result = hand.sfen();
 
         assert_eq!(result, "pbr");
@@ -220,12 +238,11 @@ mod tests {
 
     #[test]
     pub fn test_hand_all_two() {
-        let hashmap = HashMap::from([
+        let hand = Hand::from([
             (HandPiece::Bishop, 2),
             (HandPiece::Rook, 2),
             (HandPiece::Pawn, 2),
         ]);
-        let hand = Hand(hashmap);
         let result = hand.sfen();
 
         assert_eq!(result, "p2b2r2");
@@ -233,12 +250,11 @@ mod tests {
 
     #[test]
     pub fn test_hand_mixed() {
-        let hashmap = HashMap::from([
+        let hand = Hand::from([
             (HandPiece::Bishop, 0),
             (HandPiece::Rook, 1),
             (HandPiece::Pawn, 2),
         ]);
-        let hand = Hand(hashmap);
         let result = hand.sfen();
 
         assert_eq!(result, "p2r");
@@ -246,30 +262,7 @@ mod tests {
 
     #[test]
     pub fn test_position_starting() {
-        let board = Board([
-            Some(WHITE_ROOK),
-            Some(WHITE_KING),
-            Some(WHITE_BISHOP),
-            //-----
-            None,
-            Some(WHITE_PAWN),
-            None,
-            //-----
-            None,
-            Some(BLACK_PAWN),
-            None,
-            // ----
-            Some(BLACK_BISHOP),
-            Some(BLACK_KING),
-            Some(BLACK_ROOK),
-        ]);
-
-        let position = Position {
-            board,
-            to_play: Color::Black,
-            black_hand: Hand(HashMap::new()),
-            white_hand: Hand(HashMap::new()),
-        };
+        let position = Position::startpos();
 
         let result = position.sfen();
 
@@ -296,21 +289,35 @@ mod tests {
             Some(BLACK_ROOK),
         ]);
 
-        let black_hashmap = HashMap::from([(HandPiece::Rook, 1), (HandPiece::Pawn, 2)]);
-        let black_hand = Hand(black_hashmap);
+        let black_hand = Hand::from([(HandPiece::Rook, 1), (HandPiece::Pawn, 2)]);
 
-        let white_hashmap = HashMap::from([(HandPiece::Bishop, 2)]);
-        let white_hand = Hand(white_hashmap);
+        let white_hand = Hand::from([(HandPiece::Bishop, 2)]);
 
-        let position = Position {
-            board,
-            to_play: Color::White,
-            black_hand,
-            white_hand,
-        };
+        let position = Position::new(board, Color::White, black_hand, white_hand);
 
         let result = position.sfen();
 
         assert_eq!(result, "1k1/3/3/1KR w P2Rb2");
     }
+
+    #[test]
+    pub fn test_position_display() {
+        let position = Position::startpos();
+
+        assert_eq!(position.to_string(), "rkb/1p1/1P1/BKR b -");
+    }
+
+    #[test]
+    pub fn test_position_with_move_number() {
+        let position = Position::startpos().with_move_number(12);
+
+        assert_eq!(position.sfen(), "rkb/1p1/1P1/BKR b - 12");
+    }
+
+    #[test]
+    pub fn test_bitboard() {
+        let bitboard = BitBoard::from(Position::startpos().board());
+
+        assert_eq!(bitboard.sfen(), "rkb/1p1/1P1/BKR");
+    }
 }
@@ -0,0 +1,550 @@
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::Path;
+
+use crate::game::*;
+
+const MAGIC: &[u8; 8] = b"LEWEKDB2";
+
+/// Game theoretical value of a position, for the side to play.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Outcome {
+    Win,
+    Loss,
+    Draw,
+}
+
+/// Result of perfect play from a position.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Solution {
+    pub outcome: Outcome,
+    /// Number of plies until the end of the game, zero for draws.
+    pub distance: u32,
+    /// Move reaching the fastest win, the slowest loss or keeping the draw.
+    pub best_move: Option<Move>,
+}
+
+/// Progress of [`Database::solve_with_progress`].
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Progress {
+    /// Size of the table, one entry per index of a position with both lions.
+    Indexed { positions: u64 },
+    /// Positions won or lost in exactly `distance` plies.
+    Solved { distance: u32, positions: usize },
+}
+
+/// Perfect play database of every position of the game.
+///
+/// Positions with both lions on the board are stored by their
+/// [`IndexMode::Mirror`] index, so that a position and its mirror image share
+/// one entry, with one byte holding the distance to the end of the game plus
+/// one, or zero for draws. The side to play wins when the distance is odd and
+/// loses when it is even. Positions without both lions are over and not
+/// stored.
+pub struct Database {
+    material: Material,
+    indexer: Indexer,
+    values: Vec<u8>,
+}
+
+/// Whether the side to play has lost already.
+fn is_over(position: &Position) -> bool {
+    position.result() != GameResult::Ongoing || position.legal_moves().is_empty()
+}
+
+/// Positions from which a move of the side which just moved reaches
+/// `position`, found by taking that move back. Positions in which the game
+/// was already over are left out.
+fn predecessors(position: &Position) -> Vec<Position> {
+    let mover = position.to_play().opposite();
+    let mut previous = *position;
+    previous.set_to_play(mover);
+
+    let board = *position.board();
+    let mut predecessors = vec![];
+    for to in ALL_INDEXES {
+        let Some(piece) = board[to] else {
+            continue;
+        };
+        if piece.color != mover {
+            continue;
+        }
+
+        // A chick reaching the last row always promotes, so a chick there
+        // was dropped and a hen there may have been a chick before the move.
+        let (row, _) = field2rowcol(to);
+        let last_row = row == promotion_row(mover);
+        let mut moved = vec![];
+        if !(last_row && piece.kind == PieceKind::Pawn) {
+            moved.push(piece);
+        }
+        if last_row && piece.kind == PieceKind::PromotedPawn {
+            moved.push(Piece {
+                kind: PieceKind::Pawn,
+                color: mover,
+            });
+        }
+
+        // A captured chick goes to the hand whether or not it had promoted.
+        let mut captured = vec![None];
+        for (hand_piece, _) in position.hand(mover).iter() {
+            let color = mover.opposite();
+            captured.push(Some(Piece {
+                kind: hand_piece.into(),
+                color,
+            }));
+            if hand_piece == HandPiece::Pawn {
+                captured.push(Some(Piece {
+                    kind: PieceKind::PromotedPawn,
+                    color,
+                }));
+            }
+        }
+
+        for &before in &moved {
+            for from in ALL_INDEXES {
+                if board[from].is_some() || attacks(from, before) & (1 << to) == 0 {
+                    continue;
+                }
+                for &captured in &captured {
+                    let mut predecessor = previous;
+                    predecessor.set_piece(from, Some(before));
+                    predecessor.set_piece(to, captured);
+                    if let Some(captured) = captured {
+                        let mut hand = *predecessor.hand(mover);
+                        hand.remove(HandPiece::try_from(captured.kind).unwrap());
+                        predecessor.set_hand(mover, hand);
+                    }
+                    predecessors.push(predecessor);
+                }
+            }
+        }
+
+        if let Ok(hand_piece) = HandPiece::try_from(piece.kind) {
+            if piece.kind != PieceKind::PromotedPawn {
+                let mut predecessor = previous;
+                predecessor.set_piece(to, None);
+                let mut hand = *predecessor.hand(mover);
+                hand.add(hand_piece);
+                predecessor.set_hand(mover, hand);
+                predecessors.push(predecessor);
+            }
+        }
+    }
+
+    predecessors.retain(|predecessor| predecessor.result() == GameResult::Ongoing);
+    predecessors
+}
+
+impl Database {
+    /// Solves every position of the game by retrograde analysis.
+    ///
+    /// This needs one byte of memory for each of the roughly 1.3 billion
+    /// indexes of positions with both lions, and a scan of all of them for
+    /// every distance to the end of the game.
+    pub fn solve() -> Self {
+        Database::solve_with_progress(|_| {})
+    }
+
+    pub fn solve_with_progress(progress: impl FnMut(Progress)) -> Self {
+        Database::solve_material(GAME_MATERIAL, progress)
+    }
+
+    /// Solves every position with the given material, which is smaller than
+    /// the material of a game for endgames.
+    pub(crate) fn solve_material(material: Material, mut progress: impl FnMut(Progress)) -> Self {
+        let indexer = Indexer::new(material, IndexMode::Mirror);
+        let mut database = Database {
+            material,
+            values: vec![0; indexer.lions_size() as usize],
+            indexer,
+        };
+        progress(Progress::Indexed {
+            positions: database.values.len() as u64,
+        });
+        database.retrograde(&mut progress);
+        database
+    }
+
+    fn retrograde(&mut self, progress: &mut impl FnMut(Progress)) {
+        let mut solved = 0;
+        for index in 0..self.values.len() {
+            let Some(position) = self.indexer.position(index as u64) else {
+                continue;
+            };
+            if is_over(&position) {
+                self.values[index] = 1;
+                solved += 1;
+            }
+        }
+        progress(Progress::Solved {
+            distance: 0,
+            positions: solved,
+        });
+
+        // Wins in one ply mostly capture a lion, reaching positions which are
+        // not stored, so they are found by looking at the moves.
+        let mut solved = 0;
+        for index in 0..self.values.len() {
+            if self.values[index] != 0 {
+                continue;
+            }
+            let Some(position) = self.indexer.position(index as u64) else {
+                continue;
+            };
+            if self.children(&position).any(|(_, child)| child == Some(0)) {
+                self.values[index] = 2;
+                solved += 1;
+            }
+        }
+        progress(Progress::Solved {
+            distance: 1,
+            positions: solved,
+        });
+
+        let mut distance = 2;
+        while solved > 0 {
+            assert!(
+                distance < u8::MAX as u32,
+                "distance beyond what the database can hold"
+            );
+            let previous: Vec<usize> = (0..self.values.len())
+                .filter(|&index| self.values[index] as u32 == distance)
+                .collect();
+
+            solved = 0;
+            for index in previous {
+                let position = self.indexer.position(index as u64).unwrap();
+                for predecessor in predecessors(&position) {
+                    let predecessor_index = self.indexer.index(&predecessor) as usize;
+                    if self.values[predecessor_index] != 0 {
+                        continue;
+                    }
+                    // Won if some move leads to a position lost for the
+                    // opponent, lost if every move leads to a position won
+                    // for the opponent.
+                    let resolved = distance % 2 == 1
+                        || self
+                            .children(&predecessor)
+                            .all(|(_, child)| child.is_some_and(|d| d % 2 == 1));
+                    if resolved {
+                        self.values[predecessor_index] = distance as u8 + 1;
+                        solved += 1;
+                    }
+                }
+            }
+
+            progress(Progress::Solved {
+                distance,
+                positions: solved,
+            });
+            distance += 1;
+        }
+    }
+
+    /// Moves of the position with the distances of the positions they reach.
+    fn children<'a>(
+        &'a self,
+        position: &'a Position,
+    ) -> impl Iterator<Item = (Move, Option<u32>)> + 'a {
+        position.legal_moves().into_iter().map(|mv| {
+            let mut child = *position;
+            child.make_move(mv);
+            (mv, self.distance(&child))
+        })
+    }
+
+    /// Distance of a position with the material of the database, `None` for
+    /// draws.
+    fn distance(&self, position: &Position) -> Option<u32> {
+        if position.king_field(Color::Black).is_none()
+            || position.king_field(Color::White).is_none()
+        {
+            return Some(0);
+        }
+        match self.values[self.indexer.index(position) as usize] {
+            0 => None,
+            value => Some(value as u32 - 1),
+        }
+    }
+
+    /// Number of stored entries.
+    pub fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+
+    /// Value of the position without looking for the best move,
+    /// or `None` if the position has other material than the database.
+    pub fn value(&self, position: &Position) -> Option<(Outcome, u32)> {
+        if position.material() != self.material {
+            return None;
+        }
+        Some(match self.distance(position) {
+            None => (Outcome::Draw, 0),
+            Some(distance) if distance % 2 == 1 => (Outcome::Win, distance),
+            Some(distance) => (Outcome::Loss, distance),
+        })
+    }
+
+    pub fn probe(&self, position: &Position) -> Option<Solution> {
+        let (outcome, distance) = self.value(position)?;
+
+        let best_move = if is_over(position) {
+            None
+        } else {
+            let mut candidates = self.children(position);
+            match outcome {
+                Outcome::Win | Outcome::Loss => candidates
+                    .find(|&(_, child)| child == Some(distance - 1))
+                    .map(|(mv, _)| mv),
+                Outcome::Draw => candidates
+                    .find(|&(_, child)| child.is_none())
+                    .map(|(mv, _)| mv),
+            }
+        };
+
+        Some(Solution {
+            outcome,
+            distance,
+            best_move,
+        })
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        writer.write_all(MAGIC)?;
+        writer.write_all(&self.material.map(|count| count as u8))?;
+        writer.write_all(&(self.values.len() as u64).to_le_bytes())?;
+        writer.write_all(&self.values)?;
+        writer.flush()
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> io::Result<Self> {
+        let mut reader = BufReader::new(File::open(path)?);
+        let invalid = |message| io::Error::new(io::ErrorKind::InvalidData, message);
+
+        let mut magic = [0; 8];
+        reader.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            return Err(invalid("not a lewek database"));
+        }
+
+        let mut material = [0; 3];
+        reader.read_exact(&mut material)?;
+        if material
+            .iter()
+            .any(|&count| count as usize > MAX_HAND_COUNT)
+        {
+            return Err(invalid("database material out of range"));
+        }
+        let material = material.map(|count| count as usize);
+        let indexer = Indexer::new(material, IndexMode::Mirror);
+
+        let mut buffer = [0; 8];
+        reader.read_exact(&mut buffer)?;
+        if u64::from_le_bytes(buffer) != indexer.lions_size() {
+            return Err(invalid("database size does not match its material"));
+        }
+
+        let mut values = vec![0; indexer.lions_size() as usize];
+        reader.read_exact(&mut values)?;
+
+        Ok(Database {
+            material,
+            indexer,
+            values,
+        })
+    }
+}
+
+impl Position {
+    /// Perfect play result of the position according to the database.
+    pub fn probe(&self, database: &Database) -> Option<Solution> {
+        database.probe(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::search::{search, Score, SearchLimits};
+
+    /// Endgames lack material, so they are set up without the SFEN parser.
+    fn endgame(board: &str, to_play: Color, black_hand: Hand) -> Position {
+        let board: BitBoard = board.parse().unwrap();
+        Position::new(Board::from(&board), to_play, black_hand, Hand::empty())
+    }
+
+    fn solve(material: Material) -> Database {
+        Database::solve_material(material, |_| {})
+    }
+
+    #[test]
+    fn test_predecessors() {
+        let indexer = Indexer::new([1, 0, 0], IndexMode::Full);
+        for index in 0..indexer.lions_size() {
+            let position = indexer.position(index).unwrap();
+            if position.result() != GameResult::Ongoing {
+                continue;
+            }
+            for mv in position.legal_moves() {
+                let mut child = position;
+                child.make_move(mv);
+                if child.king_field(Color::Black).is_some()
+                    && child.king_field(Color::White).is_some()
+                {
+                    assert!(
+                        predecessors(&child).contains(&position),
+                        "{} after {:?}",
+                        position,
+                        mv
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_predecessors_reach_the_position() {
+        let indexer = Indexer::new([1, 0, 0], IndexMode::Full);
+        for index in (0..indexer.lions_size()).step_by(3) {
+            let position = indexer.position(index).unwrap();
+            for predecessor in predecessors(&position) {
+                assert!(
+                    predecessor.legal_moves().into_iter().any(|mv| {
+                        let mut child = predecessor;
+                        child.make_move(mv);
+                        child == position
+                    }),
+                    "{} from {}",
+                    position,
+                    predecessor
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_immediate_try() {
+        let root = endgame("2k/K2/3/3", Color::Black, Hand::empty());
+        let database = solve([0, 0, 0]);
+
+        let solution = root.probe(&database).unwrap();
+
+        assert_eq!(solution.outcome, Outcome::Win);
+        assert_eq!(solution.distance, 1);
+        assert_eq!(solution.best_move, Some(Move::Board { from: 3, to: 0 }));
+    }
+
+    #[test]
+    fn test_lost_position() {
+        let mut root = endgame("1k1/3/1R1/1K1", Color::White, Hand::empty());
+        root.make_move(Move::Board { from: 1, to: 4 });
+        let database = solve([0, 0, 1]);
+
+        assert_eq!(database.value(&root), Some((Outcome::Win, 1)));
+
+        let mut lost = root;
+        lost.make_move(Move::Board { from: 7, to: 4 });
+        assert_eq!(
+            lost.probe(&database),
+            Some(Solution {
+                outcome: Outcome::Loss,
+                distance: 0,
+                best_move: None,
+            })
+        );
+    }
+
+    #[test]
+    fn test_other_material() {
+        let database = solve([0, 0, 0]);
+
+        assert_eq!(Position::startpos().probe(&database), None);
+    }
+
+    #[test]
+    fn test_agrees_with_search() {
+        let database = solve([1, 0, 0]);
+
+        let mut checked = 0;
+        for index in (0..database.len() as u64).step_by(7) {
+            let Some(position) = database.indexer.position(index) else {
+                continue;
+            };
+            if position.result() != GameResult::Ongoing {
+                continue;
+            }
+            let (outcome, distance) = database.value(&position).unwrap();
+            if outcome == Outcome::Draw || distance > 5 {
+                continue;
+            }
+
+            let result = search(&position, SearchLimits::depth(distance as usize + 1));
+            let expected = match outcome {
+                Outcome::Win => Score::Win(distance),
+                _ => Score::Loss(distance),
+            };
+            assert_eq!(result.score(), expected, "{}", position);
+            checked += 1;
+        }
+        assert!(checked > 50);
+    }
+
+    #[test]
+    fn test_best_moves_keep_the_value() {
+        let database = solve([0, 1, 0]);
+
+        for index in 0..database.len() as u64 {
+            let Some(position) = database.indexer.position(index) else {
+                continue;
+            };
+            let solution = database.probe(&position).unwrap();
+            let Some(best_move) = solution.best_move else {
+                continue;
+            };
+
+            let mut child = position;
+            child.make_move(best_move);
+            let (child_outcome, child_distance) = database.value(&child).unwrap();
+            match solution.outcome {
+                Outcome::Win => assert_eq!(child_outcome, Outcome::Loss),
+                Outcome::Loss => assert_eq!(child_outcome, Outcome::Win),
+                Outcome::Draw => assert_eq!(child_outcome, Outcome::Draw),
+            }
+            if solution.outcome != Outcome::Draw {
+                assert_eq!(child_distance + 1, solution.distance);
+            }
+        }
+    }
+
+    #[test]
+    fn test_save_and_load() {
+        let root = endgame("1k1/3/3/1K1", Color::Black, Hand::empty());
+        let database = solve([0, 0, 0]);
+        let path = std::env::temp_dir().join(format!("lewek-db-{}.bin", std::process::id()));
+
+        database.save(&path).unwrap();
+        let loaded = Database::load(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(loaded.material, database.material);
+        assert_eq!(loaded.values, database.values);
+        assert_eq!(loaded.probe(&root), database.probe(&root));
+    }
+
+    #[test]
+    fn test_load_rejects_other_files() {
+        let path = std::env::temp_dir().join(format!("lewek-not-db-{}.bin", std::process::id()));
+        std::fs::write(&path, b"definitely not a database").unwrap();
+
+        let result = Database::load(&path);
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(result.err().unwrap().kind(), io::ErrorKind::InvalidData);
+    }
+}
@@ -0,0 +1,235 @@
+use crate::game::*;
+use crate::search::WIN_THRESHOLD;
+
+pub const DEFAULT_TT_SIZE_MB: usize = 16;
+
+/// How the stored score relates to the real score of the position.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Bound {
+    Exact,
+    /// The real score is at least the stored one (the search failed high).
+    Lower,
+    /// The real score is at most the stored one (the search failed low).
+    Upper,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct TtEntry {
+    pub key: u64,
+    pub best_move: Option<Move>,
+    pub score: i32,
+    pub depth: u8,
+    pub bound: Bound,
+    age: u8,
+}
+
+/// Fixed size hash table of search results, indexed by Zobrist keys.
+///
+/// An entry is replaced by a search of the same or greater depth,
+/// or by any search once it is left over from a previous one.
+pub struct TranspositionTable {
+    entries: Vec<Option<TtEntry>>,
+    age: u8,
+}
+
+impl TranspositionTable {
+    pub fn new(size_mb: usize) -> Self {
+        let bytes = size_mb.max(1) * 1024 * 1024;
+        let count = bytes / std::mem::size_of::<Option<TtEntry>>();
+        // Round down to a power of two, so that indexing is a mask.
+        let count = 1 << (usize::BITS - 1 - count.leading_zeros());
+        TranspositionTable {
+            entries: vec![None; count],
+            age: 0,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.fill(None);
+        self.age = 0;
+    }
+
+    /// Marks all entries as coming from a previous search.
+    pub fn new_search(&mut self) {
+        self.age = self.age.wrapping_add(1);
+    }
+
+    fn index(&self, key: u64) -> usize {
+        key as usize & (self.entries.len() - 1)
+    }
+
+    /// Looks the position up, with win and loss scores made
+    /// relative to the root again.
+    pub fn probe(&self, key: u64, ply: usize) -> Option<TtEntry> {
+        let mut entry = self.entries[self.index(key)].filter(|entry| entry.key == key)?;
+        entry.score = score_from_tt(entry.score, ply);
+        Some(entry)
+    }
+
+    pub fn store(
+        &mut self,
+        key: u64,
+        depth: usize,
+        score: i32,
+        bound: Bound,
+        best_move: Option<Move>,
+        ply: usize,
+    ) {
+        let index = self.index(key);
+        let replace = match self.entries[index] {
+            None => true,
+            Some(entry) => {
+                entry.key == key || entry.age != self.age || depth >= entry.depth as usize
+            }
+        };
+        if !replace {
+            return;
+        }
+
+        let best_move = best_move.or_else(|| {
+            self.entries[index]
+                .filter(|entry| entry.key == key)
+                .and_then(|entry| entry.best_move)
+        });
+        self.entries[index] = Some(TtEntry {
+            key,
+            best_move,
+            score: score_to_tt(score, ply),
+            depth: depth.min(u8::MAX as usize) as u8,
+            bound,
+            age: self.age,
+        });
+    }
+}
+
+impl Default for TranspositionTable {
+    fn default() -> Self {
+        TranspositionTable::new(DEFAULT_TT_SIZE_MB)
+    }
+}
+
+/// Win and loss scores count plies from the root. In the table they
+/// are stored counting from the position itself, as the same position
+/// can be reached at different plies.
+fn score_to_tt(score: i32, ply: usize) -> i32 {
+    if score > WIN_THRESHOLD {
+        score + ply as i32
+    } else if score < -WIN_THRESHOLD {
+        score - ply as i32
+    } else {
+        score
+    }
+}
+
+fn score_from_tt(score: i32, ply: usize) -> i32 {
+    if score > WIN_THRESHOLD {
+        score - ply as i32
+    } else if score < -WIN_THRESHOLD {
+        score + ply as i32
+    } else {
+        score
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::search::WIN_SCORE;
+
+    const MOVE: Move = Move::Board { from: 7, to: 4 };
+
+    #[test]
+    fn test_size() {
+        let table = TranspositionTable::new(1);
+
+        assert!(table.len().is_power_of_two());
+        assert!(table.len() * std::mem::size_of::<Option<TtEntry>>() <= 1024 * 1024);
+        assert!(table.len() * std::mem::size_of::<Option<TtEntry>>() > 512 * 1024);
+    }
+
+    #[test]
+    fn test_store_and_probe() {
+        let mut table = TranspositionTable::new(1);
+        let key = Position::startpos().key();
+
+        table.store(key, 3, 42, Bound::Exact, Some(MOVE), 0);
+
+        let entry = table.probe(key, 0).unwrap();
+        assert_eq!(entry.score, 42);
+        assert_eq!(entry.depth, 3);
+        assert_eq!(entry.bound, Bound::Exact);
+        assert_eq!(entry.best_move, Some(MOVE));
+        assert_eq!(table.probe(key ^ 1 << 40, 0), None);
+    }
+
+    #[test]
+    fn test_win_scores_are_relative_to_position() {
+        let mut table = TranspositionTable::new(1);
+
+        // Win in 5 plies from the root, found at ply 2.
+        table.store(1, 3, WIN_SCORE - 5, Bound::Exact, None, 2);
+        // Loss in 6 plies from the root, found at ply 4.
+        table.store(2, 3, -WIN_SCORE + 6, Bound::Exact, None, 4);
+
+        // The same positions reached at ply 1 are one ply closer to the end.
+        assert_eq!(table.probe(1, 1).unwrap().score, WIN_SCORE - 4);
+        assert_eq!(table.probe(2, 1).unwrap().score, -WIN_SCORE + 3);
+    }
+
+    #[test]
+    fn test_replace_by_depth() {
+        let mut table = TranspositionTable::new(1);
+        let key = 5;
+        let other = key + table.len() as u64;
+
+        table.store(key, 4, 10, Bound::Exact, Some(MOVE), 0);
+        table.store(other, 2, 20, Bound::Lower, None, 0);
+        assert_eq!(table.probe(key, 0).unwrap().score, 10);
+        assert_eq!(table.probe(other, 0), None);
+
+        table.store(other, 4, 20, Bound::Lower, None, 0);
+        assert_eq!(table.probe(key, 0), None);
+        assert_eq!(table.probe(other, 0).unwrap().score, 20);
+    }
+
+    #[test]
+    fn test_old_entries_are_replaced() {
+        let mut table = TranspositionTable::new(1);
+        let key = 5;
+        let other = key + table.len() as u64;
+
+        table.store(key, 8, 10, Bound::Exact, None, 0);
+        table.new_search();
+        table.store(other, 1, 20, Bound::Upper, None, 0);
+
+        assert_eq!(table.probe(other, 0).unwrap().score, 20);
+    }
+
+    #[test]
+    fn test_keeps_best_move_of_same_position() {
+        let mut table = TranspositionTable::new(1);
+
+        table.store(9, 2, 10, Bound::Exact, Some(MOVE), 0);
+        table.store(9, 3, 5, Bound::Upper, None, 0);
+
+        assert_eq!(table.probe(9, 0).unwrap().best_move, Some(MOVE));
+    }
+
+    #[test]
+    fn test_clear() {
+        let mut table = TranspositionTable::new(1);
+
+        table.store(9, 2, 10, Bound::Exact, Some(MOVE), 0);
+        table.clear();
+
+        assert_eq!(table.probe(9, 0), None);
+    }
+}
@@ -0,0 +1,239 @@
+//! Checks the move generator against a reference perft written separately
+//! from the rules, sharing no code with the crate: it reads the SFEN itself,
+//! keeps the board as a grid of characters and plays moves on copies.
+
+use lewek::{perft, Position};
+
+/// Pieces are SFEN letters, with `+` standing for a hen of either color
+/// and the color kept apart.
+#[derive(Copy, Clone, PartialEq)]
+struct Square {
+    letter: char,
+    black: bool,
+}
+
+#[derive(Copy, Clone)]
+struct Reference {
+    board: [[Option<Square>; 3]; 4],
+    /// Chicks, elephants and giraffes in the hands of Black and White.
+    hands: [[u8; 3]; 2],
+    black_to_play: bool,
+}
+
+const HAND_LETTERS: [char; 3] = ['p', 'b', 'r'];
+
+fn parse(sfen: &str) -> Reference {
+    let fields: Vec<&str> = sfen.split(' ').collect();
+    let mut board = [[None; 3]; 4];
+    for (row, text) in fields[0].split('/').enumerate() {
+        let mut col = 0;
+        let mut chars = text.chars().peekable();
+        while let Some(c) = chars.next() {
+            if let Some(empty) = c.to_digit(10) {
+                col += empty as usize;
+                continue;
+            }
+            let promoted = chars.peek() == Some(&'+');
+            if promoted {
+                chars.next();
+            }
+            board[row][col] = Some(Square {
+                letter: if promoted {
+                    '+'
+                } else {
+                    c.to_ascii_lowercase()
+                },
+                black: c.is_ascii_uppercase(),
+            });
+            col += 1;
+        }
+    }
+
+    let mut hands = [[0; 3]; 2];
+    let mut chars = fields[2].chars().peekable();
+    while let Some(c) = chars.next() {
+        if c == '-' {
+            break;
+        }
+        let count = match chars.peek().and_then(|d| d.to_digit(10)) {
+            Some(count) => {
+                chars.next();
+                count as u8
+            }
+            None => 1,
+        };
+        let kind = HAND_LETTERS
+            .iter()
+            .position(|&letter| letter == c.to_ascii_lowercase())
+            .unwrap();
+        hands[usize::from(c.is_ascii_lowercase())][kind] += count;
+    }
+
+    Reference {
+        board,
+        hands,
+        black_to_play: fields[1] == "b",
+    }
+}
+
+/// Steps as (row, column) offsets for Black, who moves towards row 0.
+fn steps(letter: char) -> &'static [(i32, i32)] {
+    match letter {
+        'k' => &[
+            (-1, -1),
+            (-1, 0),
+            (-1, 1),
+            (0, -1),
+            (0, 1),
+            (1, -1),
+            (1, 0),
+            (1, 1),
+        ],
+        'r' => &[(-1, 0), (1, 0), (0, -1), (0, 1)],
+        'b' => &[(-1, -1), (-1, 1), (1, -1), (1, 1)],
+        'p' => &[(-1, 0)],
+        '+' => &[(-1, -1), (-1, 0), (-1, 1), (0, -1), (0, 1), (1, 0)],
+        _ => unreachable!(),
+    }
+}
+
+impl Reference {
+    fn lion(&self, black: bool) -> Option<(usize, usize)> {
+        (0..4)
+            .flat_map(|row| (0..3).map(move |col| (row, col)))
+            .find(|&(row, col)| self.board[row][col] == Some(Square { letter: 'k', black }))
+    }
+
+    /// Fields a piece of the given color can step to from `(row, col)`.
+    fn targets(&self, row: usize, col: usize) -> Vec<(usize, usize)> {
+        let square = self.board[row][col].unwrap();
+        let sign = if square.black { 1 } else { -1 };
+        steps(square.letter)
+            .iter()
+            .map(|&(drow, dcol)| (row as i32 + sign * drow, col as i32 + sign * dcol))
+            .filter(|&(r, c)| (0..4).contains(&r) && (0..3).contains(&c))
+            .map(|(r, c)| (r as usize, c as usize))
+            .filter(|&(r, c)| self.board[r][c].is_none_or(|other| other.black != square.black))
+            .collect()
+    }
+
+    fn attacked_by(&self, field: (usize, usize), black: bool) -> bool {
+        (0..4).any(|row| {
+            (0..3).any(|col| {
+                self.board[row][col].is_some_and(|square| square.black == black)
+                    && self.targets(row, col).contains(&field)
+            })
+        })
+    }
+
+    /// Whether the game is over: a lion was captured, or the lion of the
+    /// side which just moved reached the last row and can't be taken.
+    fn is_over(&self) -> bool {
+        let (Some(_), Some(_)) = (self.lion(true), self.lion(false)) else {
+            return true;
+        };
+        let mover = !self.black_to_play;
+        let lion = self.lion(mover).unwrap();
+        let last_row = if mover { 0 } else { 3 };
+        lion.0 == last_row && !self.attacked_by(lion, self.black_to_play)
+    }
+
+    fn children(&self) -> Vec<Reference> {
+        let us = self.black_to_play;
+        let side = usize::from(!us);
+        let mut children = vec![];
+
+        for row in 0..4 {
+            for col in 0..3 {
+                let Some(square) = self.board[row][col] else {
+                    continue;
+                };
+                if square.black != us {
+                    continue;
+                }
+                for (to_row, to_col) in self.targets(row, col) {
+                    let mut child = *self;
+                    if let Some(captured) = child.board[to_row][to_col] {
+                        if captured.letter != 'k' {
+                            let letter = if captured.letter == '+' {
+                                'p'
+                            } else {
+                                captured.letter
+                            };
+                            let kind = HAND_LETTERS.iter().position(|&l| l == letter).unwrap();
+                            child.hands[side][kind] += 1;
+                        }
+                    }
+                    let last_row = if us { 0 } else { 3 };
+                    let mut moved = square;
+                    if moved.letter == 'p' && to_row == last_row {
+                        moved.letter = '+';
+                    }
+                    child.board[row][col] = None;
+                    child.board[to_row][to_col] = Some(moved);
+                    child.black_to_play = !us;
+                    children.push(child);
+                }
+            }
+        }
+
+        for (kind, &letter) in HAND_LETTERS.iter().enumerate() {
+            if self.hands[side][kind] == 0 {
+                continue;
+            }
+            for row in 0..4 {
+                for col in 0..3 {
+                    if self.board[row][col].is_some() {
+                        continue;
+                    }
+                    let mut child = *self;
+                    child.hands[side][kind] -= 1;
+                    child.board[row][col] = Some(Square { letter, black: us });
+                    child.black_to_play = !us;
+                    children.push(child);
+                }
+            }
+        }
+        children
+    }
+
+    fn perft(&self, depth: usize) -> u64 {
+        if depth == 0 {
+            return 1;
+        }
+        if self.is_over() {
+            return 0;
+        }
+        self.children()
+            .iter()
+            .map(|child| child.perft(depth - 1))
+            .sum()
+    }
+}
+
+#[test]
+fn test_perft_matches_reference() {
+    // Start, drops, promotions, captured hens and tries.
+    let sfens = [
+        "rkb/1p1/1P1/BKR b -",
+        "r1b/1k1/3/BPK b Pr",
+        "1k1/p2/3/1K1 w PBRbr",
+        "k2/1P1/3/2K b BRbpr",
+        "2k/1p+1/R2/K1b w PBr",
+        "2k/K2/1p1/2B b Pbr2",
+    ];
+
+    for sfen in sfens {
+        let reference = parse(sfen);
+        let mut position: Position = sfen.parse().unwrap();
+        for depth in 1..=5 {
+            assert_eq!(
+                perft(&mut position, depth),
+                reference.perft(depth),
+                "{} depth {}",
+                sfen,
+                depth
+            );
+        }
+    }
+}
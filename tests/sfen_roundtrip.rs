@@ -0,0 +1,90 @@
+use lewek::{GameResult, Position, Sfen, SfenMode};
+
+/// Small deterministic generator, so that the test needs no dependencies
+/// and always checks the same positions.
+struct XorShift(u64);
+
+impl XorShift {
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    fn below(&mut self, n: usize) -> usize {
+        (self.next() % n as u64) as usize
+    }
+}
+
+/// Plays random games from the starting position, collecting every position
+/// on the way.
+fn generate_positions(games: usize, seed: u64) -> Vec<Position> {
+    let mut rng = XorShift(seed);
+    let mut positions = vec![];
+
+    for game in 0..games {
+        let mut position = Position::startpos();
+        if game % 2 == 0 {
+            position = position.with_move_number(1);
+        }
+
+        while position.result() == GameResult::Ongoing && positions.len() < games * 100 {
+            let moves = position.legal_moves();
+            if moves.is_empty() {
+                break;
+            }
+            position.make_move(moves[rng.below(moves.len())]);
+            positions.push(position);
+        }
+    }
+
+    positions
+}
+
+#[test]
+fn test_position_roundtrip() {
+    let positions = generate_positions(200, 0x5eed);
+    assert!(positions.len() > 1000);
+
+    // Play can leave either lion attacked, and such positions have to
+    // round-trip like all others.
+    let mut lions_in_attack = [0; 2];
+    for position in positions {
+        let sfen = position.sfen();
+
+        for color in [position.to_play(), position.to_play().opposite()] {
+            if position
+                .king_field(color)
+                .is_some_and(|field| position.is_attacked(field, color.opposite()))
+            {
+                lions_in_attack[(color == position.to_play()) as usize] += 1;
+            }
+        }
+
+        let parsed = Position::from_sfen(&sfen, SfenMode::Canonical)
+            .unwrap_or_else(|e| panic!("{}: {}", sfen, e));
+
+        assert_eq!(parsed, position, "{}", sfen);
+        assert_eq!(parsed.key(), position.key(), "{}", sfen);
+    }
+    assert!(lions_in_attack.iter().all(|&count| count > 0));
+}
+
+#[test]
+fn test_sfen_roundtrip() {
+    let sfens = [
+        "rkb/1p1/1P1/BKR b -",
+        "rkb/1p1/1P1/BKR b - 1",
+        "rkb/3/1P1/BKR w P 2",
+        "1k1/1p+1/1P+1/1K1 w B2R2",
+        "3/1k1/3/1K1 b P2B2r2 40",
+        "r1b/1k1/3/BPK b Pr",
+        "k2/3/1p1/1K1 w PB2R2",
+    ];
+
+    for sfen in sfens {
+        let position: Position = sfen.parse().unwrap();
+        assert_eq!(position.to_string(), sfen);
+    }
+}